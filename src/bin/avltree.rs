@@ -19,4 +19,7 @@ fn main() {
     println!("{:#?}", &root);
     root = root.delete(7).1.unwrap();
     println!("{:#?}", root);
+    for (key, value) in root.range(3..) {
+        println!("{}: {}", key, value.borrow());
+    }
 }
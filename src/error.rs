@@ -0,0 +1,35 @@
+use std::{error::Error, fmt, io};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    DuplicateKey,
+    KeyNotFound,
+    /// The operation would remove the only node, which a bare `BinTree` cannot represent.
+    LastNode,
+    /// Input expected in ascending key order was not.
+    Unsorted,
+    CorruptStructure(String),
+    /// Reading or writing the file behind a paged tree failed.
+    Io(String),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::DuplicateKey => write!(f, "The key already exists."),
+            TreeError::KeyNotFound => write!(f, "Failed to find the key."),
+            TreeError::LastNode => write!(f, "Cannot remove the last node."),
+            TreeError::Unsorted => write!(f, "The keys are not in ascending order."),
+            TreeError::CorruptStructure(msg) => write!(f, "Corrupt structure: {}", msg),
+            TreeError::Io(msg) => write!(f, "I/O error: {}", msg),
+        }
+    }
+}
+
+impl Error for TreeError {}
+
+impl From<io::Error> for TreeError {
+    fn from(e: io::Error) -> Self {
+        TreeError::Io(e.to_string())
+    }
+}
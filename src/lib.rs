@@ -1,2 +1,3 @@
+pub mod error;
 pub mod trees;
 pub mod lists;
\ No newline at end of file
@@ -15,10 +15,9 @@ impl<T: Default> Linked<T> {
     }
 
     pub fn append(&mut self, value: T) {
-        if self.next.is_none() {
-            self.next = Some(Rc::new(RefCell::new(Linked { value, next: None })))
-        } else {
-            self.next.as_ref().unwrap().borrow_mut().append(value);
+        match self.next {
+            Some(ref next) => next.borrow_mut().append(value),
+            None => self.next = Some(Rc::new(RefCell::new(Linked { value, next: None }))),
         }
     }
 
@@ -35,4 +34,18 @@ impl<T: Default> Linked<T> {
             Self::delete_from_head(head.borrow().next.as_ref().unwrap(), n-1)
         }
     }
+
+    /// Clones the values following the head, in list order.
+    pub fn values_from_head(head: &RefCell<Linked<T>>) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::new();
+        let mut next = head.borrow().next.clone();
+        while let Some(node) = next {
+            values.push(node.borrow().value.clone());
+            next = node.borrow().next.clone();
+        }
+        values
+    }
 }
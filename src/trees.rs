@@ -1,2 +1,57 @@
+use std::{cmp, fmt};
+
+use crate::error::TreeError;
+
 pub mod bintree;
 pub mod btree;
+pub mod map;
+
+/// How an insert treats a key that is already in the tree. Each tree and map keeps one
+/// policy for all its inserts.
+pub enum Duplicates<T> {
+    /// Leave the tree as is and fail with `TreeError::DuplicateKey`.
+    Reject,
+    /// Store the new value in place of the old one.
+    Overwrite,
+    /// Let the function merge the new value into the stored one, in place.
+    Merge(fn(&mut T, T)),
+}
+
+impl<V> Duplicates<Vec<V>> {
+    /// The multimap policy: inserting `vec![value]` under a key that is already there
+    /// pushes `value` onto its values, which stay in insertion order.
+    pub fn append() -> Self {
+        Self::Merge(|old, new| old.extend(new))
+    }
+}
+
+// Derived impls would require `T` to implement the traits too.
+impl<T> Clone for Duplicates<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Duplicates<T> {}
+
+impl<T> fmt::Debug for Duplicates<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reject => f.write_str("Reject"),
+            Self::Overwrite => f.write_str("Overwrite"),
+            Self::Merge(_) => f.write_str("Merge"),
+        }
+    }
+}
+
+// Checks that the keys strictly increase.
+pub(crate) fn check_sorted<K: Ord, T>(items: &[(K, T)]) -> Result<(), TreeError> {
+    for pair in items.windows(2) {
+        match pair[0].0.cmp(&pair[1].0) {
+            cmp::Ordering::Less => {}
+            cmp::Ordering::Equal => return Err(TreeError::DuplicateKey),
+            cmp::Ordering::Greater => return Err(TreeError::Unsorted),
+        }
+    }
+    Ok(())
+}
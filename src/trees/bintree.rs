@@ -2,11 +2,24 @@ use std::{rc::Rc, cell::RefCell, fmt::Debug};
 
 use self::trunk::Trunk;
 
-pub(self) mod trunk;
+pub mod aggregate;
+mod build;
+mod check;
+mod entry;
+mod map;
+mod trunk;
+pub mod iter;
+mod order;
+mod rebalance;
 pub mod plain;
 pub mod avl;
+pub mod interval;
+pub mod rb;
+pub mod scapegoat;
+pub mod splay;
+pub mod treap;
 
 #[derive(Debug)]
-pub struct BinTree<T, K: Ord + Copy, S> {
-    pub(self) t: Rc<RefCell<Trunk<T, K, S>>>,
+pub struct BinTree<T, K: Ord + Copy, S, A = ()> {
+    pub(self) t: Rc<RefCell<Trunk<T, K, S, A>>>,
 }
\ No newline at end of file
@@ -0,0 +1,200 @@
+use std::{
+    fmt::Debug,
+    ops::{Add, Bound, RangeBounds},
+};
+
+use crate::error::TreeError;
+
+use super::{
+    avl::State,
+    iter::{above, below},
+    BinTree,
+};
+
+/// A summary of the values in a subtree, kept in every node of an AVL tree.
+///
+/// Summaries form a monoid: `combine` must be associative with `empty` as its
+/// identity, but need not be commutative, as runs are always combined in key order.
+pub trait Aggregate<T>: Clone {
+    /// The summary of no values.
+    fn empty() -> Self;
+
+    /// The summary of a single value.
+    fn of(value: &T) -> Self;
+
+    /// Combines the summary of a run of keys with that of the run right above it.
+    fn combine(&self, upper: &Self) -> Self;
+}
+
+/// No summary at all, which is what trees carry unless asked for one.
+impl<T> Aggregate<T> for () {
+    fn empty() -> Self {}
+
+    fn of(_: &T) -> Self {}
+
+    fn combine(&self, _: &Self) -> Self {}
+}
+
+/// Two summaries side by side, e.g. `(Min<T>, Max<T>)`.
+impl<T, A: Aggregate<T>, B: Aggregate<T>> Aggregate<T> for (A, B) {
+    fn empty() -> Self {
+        (A::empty(), B::empty())
+    }
+
+    fn of(value: &T) -> Self {
+        (A::of(value), B::of(value))
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        (self.0.combine(&upper.0), self.1.combine(&upper.1))
+    }
+}
+
+/// The sum of the values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sum<T>(pub T);
+
+impl<T: Copy + Default + Add<Output = T>> Aggregate<T> for Sum<T> {
+    fn empty() -> Self {
+        Self(T::default())
+    }
+
+    fn of(value: &T) -> Self {
+        Self(*value)
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        Self(self.0 + upper.0)
+    }
+}
+
+/// The number of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Count(pub usize);
+
+impl<T> Aggregate<T> for Count {
+    fn empty() -> Self {
+        Self(0)
+    }
+
+    fn of(_: &T) -> Self {
+        Self(1)
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        Self(self.0 + upper.0)
+    }
+}
+
+/// The smallest value, or `None` for no values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Min<T>(pub Option<T>);
+
+impl<T: Copy + PartialOrd> Aggregate<T> for Min<T> {
+    fn empty() -> Self {
+        Self(None)
+    }
+
+    fn of(value: &T) -> Self {
+        Self(Some(*value))
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        match (self.0, upper.0) {
+            (Some(a), Some(b)) if b < a => Self(Some(b)),
+            (None, b) => Self(b),
+            (a, _) => Self(a),
+        }
+    }
+}
+
+/// The largest value, or `None` for no values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Max<T>(pub Option<T>);
+
+impl<T: Copy + PartialOrd> Aggregate<T> for Max<T> {
+    fn empty() -> Self {
+        Self(None)
+    }
+
+    fn of(value: &T) -> Self {
+        Self(Some(*value))
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        match (self.0, upper.0) {
+            (Some(a), Some(b)) if a < b => Self(Some(b)),
+            (None, b) => Self(b),
+            (a, _) => Self(a),
+        }
+    }
+}
+
+impl<T, K: Ord + Copy, A: Aggregate<T>> BinTree<T, K, State, A> {
+    /// Combines the values whose keys fall into `range`, in key order, in O(log n).
+    ///
+    /// Summaries are kept up to date by the tree's own operations. A value edited
+    /// through its cell needs a `refresh` of its key before it is summarized again.
+    pub fn aggregate<R: RangeBounds<K>>(&self, range: R) -> A {
+        Self::fold(
+            Some(self),
+            &range.start_bound().cloned(),
+            &range.end_bound().cloned(),
+        )
+    }
+
+    // Below the node where the bounds part ways, one bound is unbounded, so one of
+    // the two branches is taken whole from its summary and only one path is followed.
+    fn fold(node: Option<&Self>, lower: &Bound<K>, upper: &Bound<K>) -> A {
+        let Some(node) = node else {
+            return A::empty();
+        };
+        let trunk = node.t.borrow();
+        if let (Bound::Unbounded, Bound::Unbounded) = (lower, upper) {
+            return trunk.aggregate.clone();
+        }
+        if !above(&trunk.key, lower) {
+            return Self::fold(trunk.right.as_ref(), lower, upper);
+        }
+        if !below(&trunk.key, upper) {
+            return Self::fold(trunk.left.as_ref(), lower, upper);
+        }
+        let left = Self::fold(trunk.left.as_ref(), lower, &Bound::Unbounded);
+        let right = Self::fold(trunk.right.as_ref(), &Bound::Unbounded, upper);
+        let own = A::of(&trunk.value.borrow());
+        left.combine(&own).combine(&right)
+    }
+
+    /// Recomputes the summaries on the path to `key`, after its value has been edited
+    /// through its cell. Returns whether the key was found.
+    pub fn refresh(&self, key: K) -> bool {
+        self.update_path(key)
+    }
+}
+
+impl<T, K: Ord + Copy + Debug, A: Aggregate<T> + PartialEq + Debug> BinTree<T, K, State, A> {
+    /// Verifies that every node summarizes the values below it.
+    pub fn check_aggregates(&self) -> Result<(), TreeError> {
+        self.summarize().map(|_| ())
+    }
+
+    fn summarize(&self) -> Result<A, TreeError> {
+        let trunk = self.t.borrow();
+        let left = match trunk.left.as_ref() {
+            Some(l) => l.summarize()?,
+            None => A::empty(),
+        };
+        let right = match trunk.right.as_ref() {
+            Some(r) => r.summarize()?,
+            None => A::empty(),
+        };
+        let expected = left.combine(&A::of(&trunk.value.borrow())).combine(&right);
+        if trunk.aggregate != expected {
+            return Err(TreeError::CorruptStructure(format!(
+                "node {:?} summarizes {:?} instead of {:?}",
+                trunk.key, trunk.aggregate, expected
+            )));
+        }
+        Ok(expected)
+    }
+}
@@ -1,75 +1,149 @@
-pub(self) mod rotation;
+mod avl_map;
+mod join;
+mod rotation;
 
 use core::panic;
 use std::{cell::RefCell, rc::Rc};
 
-use super::{trunk::Trunk, BinTree};
+use crate::error::TreeError;
 
+use super::{aggregate::Aggregate, trunk::Trunk, BinTree};
+
+pub use self::avl_map::AvlMap;
+pub use super::entry::{Entry, OccupiedEntry, VacantEntry};
+
+/// The operations of an AVL tree. The consuming ones hand back the new root inside an
+/// `Event`.
+///
+/// Trees keeping summaries have the same operations through `AugmentedAvl`. This trait
+/// is only implemented for trees without them, so that `BinTree::new` needs no type
+/// annotation.
 pub trait Avl<T, K: Ord + Copy> {
     fn new(key: K, value: T) -> Self;
 
-    fn delete(self: Self, key: K) -> (bool, Event<T, K>);
+    fn delete(self, key: K) -> (bool, Event<T, K>);
 
     fn find(&self, cand: K) -> (bool, K);
 
-    fn insert(self: Self, key: K, value: T) -> Event<T, K>;
+    fn insert(self, key: K, value: T) -> Event<T, K>;
+
+    /// Like `delete`, but reports a missing key as `TreeError::KeyNotFound`.
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Event<T, K>);
+
+    /// Like `insert`, but leaves the tree untouched and reports
+    /// `TreeError::DuplicateKey` instead of panicking.
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Event<T, K>);
+
+    /// Returns the cell holding the value for `key`.
+    /// The cell is shared with the tree, so `borrow_mut` on it edits the value in place.
+    /// A plain `&T` cannot be handed out instead, as the nodes sit in `RefCell`s.
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>>;
+
+    /// Edits the value for `key` in place and returns what `edit` returns. Unlike a
+    /// `borrow_mut` on the cell from `get`, it keeps the summaries above the key up to
+    /// date.
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R>;
+
+    fn contains_key(&self, key: &K) -> bool;
+
+    fn entry(self, key: K) -> Entry<T, K>;
+
+    /// Removes the smallest pair. The event is `Shrunk(None)` once the tree runs empty.
+    fn pop_first(self) -> (Pair<T, K>, Event<T, K>);
+
+    /// Removes the largest pair. The event is `Shrunk(None)` once the tree runs empty.
+    fn pop_last(self) -> (Pair<T, K>, Event<T, K>);
+}
+
+/// The operations of `Avl` on trees that also keep an `Aggregate` of their values. See
+/// `Avl` for what each of them does.
+pub trait AugmentedAvl<T, K: Ord + Copy, A> {
+    fn new(key: K, value: T) -> Self;
+
+    fn delete(self, key: K) -> (bool, Event<T, K, A>);
+
+    fn find(&self, cand: K) -> (bool, K);
+
+    fn insert(self, key: K, value: T) -> Event<T, K, A>;
+
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Event<T, K, A>);
+
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Event<T, K, A>);
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>>;
+
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R>;
+
+    fn contains_key(&self, key: &K) -> bool;
+
+    fn entry(self, key: K) -> Entry<T, K, A>;
+
+    fn pop_first(self) -> (Pair<T, K>, Event<T, K, A>);
+
+    fn pop_last(self) -> (Pair<T, K>, Event<T, K, A>);
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A key together with the cell holding its value.
+pub type Pair<T, K> = (K, Rc<RefCell<T>>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     LeftSided,
     Balanced,
     RightSided,
 }
 
-pub enum Event<T, K: Ord + Copy> {
-    None(BinTree<T, K, State>),
-    Grown(BinTree<T, K, State>),
-    Shrunk(Option<BinTree<T, K, State>>),
+pub enum Event<T, K: Ord + Copy, A = ()> {
+    None(BinTree<T, K, State, A>),
+    Grown(BinTree<T, K, State, A>),
+    Shrunk(Option<BinTree<T, K, State, A>>),
 }
 
-impl<T, K: Ord + Copy> Event<T, K> {
-    pub fn unwrap(self: Self) -> BinTree<T, K, State> {
+impl<T, K: Ord + Copy, A: Aggregate<T>> Event<T, K, A> {
+    pub fn unwrap(self) -> BinTree<T, K, State, A> {
         match self {
             Self::None(t) => t,
             Self::Grown(t) => t,
             Self::Shrunk(t) => t.unwrap(),
         }
     }
+
+    /// The resulting tree, or `None` if the last node has been deleted.
+    pub fn into_option(self) -> Option<BinTree<T, K, State, A>> {
+        match self {
+            Self::None(t) => Some(t),
+            Self::Grown(t) => Some(t),
+            Self::Shrunk(t) => t,
+        }
+    }
 }
 
-impl<T, K: Ord + Copy> BinTree<T, K, State> {
-    fn take_rightmost_leaf(self: Self) -> (Event<T, K>, Self) {
+impl<T, K: Ord + Copy, A: Aggregate<T>> BinTree<T, K, State, A> {
+    fn take_rightmost_leaf(self) -> (Event<T, K, A>, Self) {
         let right = self.t.borrow_mut().right.take().expect("No right branch.");
         if right.t.borrow().right.is_none() {
-            let state = self.t.borrow().state;
-            match state {
-                State::Balanced => {
-                    self.t.borrow_mut().state = State::LeftSided;
-                    (Event::None(self), right)
-                }
-                State::RightSided => {
-                    self.t.borrow_mut().state = State::Balanced;
-                    (Event::Shrunk(None), right)
-                }
-                State::LeftSided => {
-                    panic!("Contradiction: cannot be left sided as right leaf exists.")
-                }
-            }
+            // The left branch of the rightmost leaf takes over its position.
+            let left_of_right = right.t.borrow_mut().left.take();
+            (
+                self.remerge_right_branch(Event::Shrunk(left_of_right)),
+                right,
+            )
         } else {
             let (event, the_leaf) = right.take_rightmost_leaf();
             (self.remerge_right_branch(event), the_leaf)
         }
     }
 
-    fn remerge_right_branch(self: Self, event: Event<T, K>) -> Event<T, K> {
+    fn remerge_right_branch(self, event: Event<T, K, A>) -> Event<T, K, A> {
         match event {
             Event::None(new_right) => {
                 self.t.borrow_mut().right = Some(new_right);
+                self.t.borrow_mut().update();
                 Event::None(self)
             }
             Event::Shrunk(new_right) => {
                 self.t.borrow_mut().right = new_right;
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
                 match state {
                     State::LeftSided => self.rotate_to_right(),
@@ -85,6 +159,7 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
             }
             Event::Grown(new_right) => {
                 self.t.borrow_mut().right = Some(new_right);
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
                 match state {
                     State::LeftSided => {
@@ -96,23 +171,29 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
                         Event::Grown(self)
                     }
                     State::RightSided => {
-                        // return None as rotating rolls back the state before insertion.
-                        // note that self.rotate_to_left() can return Shrunk.
-                        Event::None(self.rotate_to_left().unwrap())
+                        // Rotating rolls back the growth unless the grown branch is balanced,
+                        // which insertion never produces but joining trees does.
+                        match self.rotate_to_left() {
+                            Event::Shrunk(Some(t)) => Event::None(t),
+                            Event::None(t) => Event::Grown(t),
+                            _ => panic!("Rotation cannot empty a tree."),
+                        }
                     }
                 }
             }
         }
     }
 
-    fn remerge_left_branch(self: Self, event: Event<T, K>) -> Event<T, K> {
+    fn remerge_left_branch(self, event: Event<T, K, A>) -> Event<T, K, A> {
         match event {
             Event::None(new_left) => {
                 self.t.borrow_mut().left = Some(new_left);
+                self.t.borrow_mut().update();
                 Event::None(self)
             }
             Event::Shrunk(new_left) => {
                 self.t.borrow_mut().left = new_left;
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
                 match state {
                     State::LeftSided => {
@@ -128,12 +209,17 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
             }
             Event::Grown(new_left) => {
                 self.t.borrow_mut().left = Some(new_left);
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
                 match state {
                     State::LeftSided => {
-                        // return None as rotating rolls back the state before insertion.
-                        // note that self.rotate_to_right() can return Shrunk.
-                        Event::None(self.rotate_to_right().unwrap())
+                        // Rotating rolls back the growth unless the grown branch is balanced,
+                        // which insertion never produces but joining trees does.
+                        match self.rotate_to_right() {
+                            Event::Shrunk(Some(t)) => Event::None(t),
+                            Event::None(t) => Event::Grown(t),
+                            _ => panic!("Rotation cannot empty a tree."),
+                        }
                     }
                     State::Balanced => {
                         self.t.borrow_mut().state = State::LeftSided;
@@ -149,25 +235,36 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
     }
 }
 
-impl<T, K: Ord + Copy> Avl<T, K> for BinTree<T, K, State> {
+impl<T, K: Ord + Copy, A: Aggregate<T>> AugmentedAvl<T, K, A> for BinTree<T, K, State, A> {
     fn new(key: K, value: T) -> Self {
+        let aggregate = A::of(&value);
         Self {
             t: Rc::new(RefCell::new(Trunk {
                 key,
-                value,
+                value: Rc::new(RefCell::new(value)),
                 left: None,
                 right: None,
                 state: State::Balanced,
+                size: 1,
+                aggregate,
             })),
         }
     }
 
-    fn insert(self: Self, key: K, value: T) -> Event<T, K> {
+    fn insert(self, key: K, value: T) -> Event<T, K, A> {
+        match self.try_insert(key, value) {
+            (Ok(()), event) => event,
+            (Err(e), _) => panic!("{}", e),
+        }
+    }
+
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Event<T, K, A>) {
         if key < self.t.borrow().key {
             if self.t.borrow().left.is_none() {
                 self.t.borrow_mut().left = Some(Self::new(key, value));
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
-                match state {
+                let event = match state {
                     State::LeftSided => self.rotate_to_right(),
                     State::Balanced => {
                         self.t.borrow_mut().state = State::LeftSided;
@@ -177,16 +274,19 @@ impl<T, K: Ord + Copy> Avl<T, K> for BinTree<T, K, State> {
                         self.t.borrow_mut().state = State::Balanced;
                         Event::None(self)
                     }
-                }
+                };
+                (Ok(()), event)
             } else {
-                let event = self.t.borrow_mut().left.take().unwrap().insert(key, value);
-                self.remerge_left_branch(event)
+                let left = self.t.borrow_mut().left.take().unwrap();
+                let (result, event) = left.try_insert(key, value);
+                (result, self.remerge_left_branch(event))
             }
         } else if self.t.borrow().key < key {
             if self.t.borrow().right.is_none() {
                 self.t.borrow_mut().right = Some(Self::new(key, value));
+                self.t.borrow_mut().update();
                 let state = self.t.borrow().state;
-                match state {
+                let event = match state {
                     State::LeftSided => {
                         self.t.borrow_mut().state = State::Balanced;
                         Event::None(self)
@@ -196,17 +296,27 @@ impl<T, K: Ord + Copy> Avl<T, K> for BinTree<T, K, State> {
                         Event::Grown(self)
                     }
                     State::RightSided => self.rotate_to_left(),
-                }
+                };
+                (Ok(()), event)
             } else {
-                let event = self.t.borrow_mut().right.take().unwrap().insert(key, value);
-                self.remerge_right_branch(event)
+                let right = self.t.borrow_mut().right.take().unwrap();
+                let (result, event) = right.try_insert(key, value);
+                (result, self.remerge_right_branch(event))
             }
         } else {
-            panic!("The key already exists.")
+            // The tree stays as it is; the nodes above only take their branch back.
+            (Err(TreeError::DuplicateKey), Event::None(self))
         }
     }
 
-    fn delete(self: Self, key: K) -> (bool, Event<T, K>) {
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Event<T, K, A>) {
+        match self.delete(key) {
+            (true, event) => (Ok(()), event),
+            (false, event) => (Err(TreeError::KeyNotFound), event),
+        }
+    }
+
+    fn delete(self, key: K) -> (bool, Event<T, K, A>) {
         if self.t.borrow().key == key {
             if self.t.borrow().left.is_none() {
                 (true, Event::Shrunk(self.t.borrow_mut().right.take()))
@@ -244,19 +354,99 @@ impl<T, K: Ord + Copy> Avl<T, K> for BinTree<T, K, State> {
     }
     fn find(&self, cand: K) -> (bool, K) {
         if self.t.borrow().key == cand {
-            return (true, cand);
+            (true, cand)
         } else if cand < self.t.borrow().key {
             if self.t.borrow().left.is_none() {
-                return (false, self.t.borrow().key);
+                (false, self.t.borrow().key)
             } else {
-                return self.t.borrow().left.as_ref().unwrap().find(cand);
+                self.t.borrow().left.as_ref().unwrap().find(cand)
             }
+        } else if self.t.borrow().right.is_none() {
+            (false, self.t.borrow().key)
         } else {
-            if self.t.borrow().right.is_none() {
-                return (false, self.t.borrow().key);
-            } else {
-                return self.t.borrow().right.as_ref().unwrap().find(cand);
-            }
+            self.t.borrow().right.as_ref().unwrap().find(cand)
         }
     }
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>> {
+        self.find_node(*key)
+            .map(|node| node.t.borrow().value.clone())
+    }
+
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let cell = self.get(key)?;
+        let result = edit(&mut cell.borrow_mut());
+        self.refresh(*key);
+        Some(result)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.find_node(*key).is_some()
+    }
+
+    fn entry(self, key: K) -> Entry<T, K, A> {
+        Entry::new(self, key, |root, key, value| root.insert(key, value).unwrap())
+    }
+
+    fn pop_first(self) -> (Pair<T, K>, Event<T, K, A>) {
+        let first = self.first();
+        let (_, event) = self.delete(first.0);
+        (first, event)
+    }
+
+    fn pop_last(self) -> (Pair<T, K>, Event<T, K, A>) {
+        let last = self.last();
+        let (_, event) = self.delete(last.0);
+        (last, event)
+    }
+}
+
+impl<T, K: Ord + Copy> Avl<T, K> for BinTree<T, K, State> {
+    fn new(key: K, value: T) -> Self {
+        AugmentedAvl::new(key, value)
+    }
+
+    fn delete(self, key: K) -> (bool, Event<T, K>) {
+        AugmentedAvl::delete(self, key)
+    }
+
+    fn find(&self, cand: K) -> (bool, K) {
+        AugmentedAvl::find(self, cand)
+    }
+
+    fn insert(self, key: K, value: T) -> Event<T, K> {
+        AugmentedAvl::insert(self, key, value)
+    }
+
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Event<T, K>) {
+        AugmentedAvl::try_delete(self, key)
+    }
+
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Event<T, K>) {
+        AugmentedAvl::try_insert(self, key, value)
+    }
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>> {
+        AugmentedAvl::get(self, key)
+    }
+
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R> {
+        AugmentedAvl::get_mut(self, key, edit)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        AugmentedAvl::contains_key(self, key)
+    }
+
+    fn entry(self, key: K) -> Entry<T, K> {
+        AugmentedAvl::entry(self, key)
+    }
+
+    fn pop_first(self) -> (Pair<T, K>, Event<T, K>) {
+        AugmentedAvl::pop_first(self)
+    }
+
+    fn pop_last(self) -> (Pair<T, K>, Event<T, K>) {
+        AugmentedAvl::pop_last(self)
+    }
 }
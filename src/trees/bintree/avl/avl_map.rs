@@ -0,0 +1,117 @@
+use std::{cell::RefCell, fmt::Debug, ops::RangeBounds, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{aggregate::Aggregate, iter::Iter, map::map_api, BinTree},
+        Duplicates,
+    },
+};
+
+use super::{AugmentedAvl, State};
+
+/// An AVL tree behind a handle that owns its root.
+///
+/// The consuming operations of `Avl` hand back a new root inside an `Event`; the map
+/// stores it in place, so callers work through `&mut self` and the tree may be empty.
+///
+/// With an `A` other than `()` every node also keeps a summary of the values below it,
+/// which `aggregate` combines over ranges of keys.
+pub struct AvlMap<K: Ord + Copy, V, A = ()> {
+    root: Option<BinTree<V, K, State, A>>,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> AvlMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            dup: Duplicates::Overwrite,
+        }
+    }
+}
+
+impl<K: Ord + Copy, V, A: Aggregate<V>> AvlMap<K, V, A> {
+    /// An empty map summarizing its values with `A`.
+    pub fn with_aggregate() -> Self {
+        Self {
+            root: None,
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Takes over an existing tree.
+    pub fn from_root(root: BinTree<V, K, State, A>) -> Self {
+        Self {
+            root: Some(root),
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, State, A>> {
+        self.root
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, State, A>> {
+        self.root.as_ref().map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // Inserts a key the map does not hold yet.
+    fn insert_new(&mut self, key: K, value: V) {
+        self.root = Some(match self.root.take() {
+            Some(root) => root.insert(key, value).unwrap(),
+            None => BinTree::new(key, value),
+        });
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        let value = self.get(&key).ok_or(TreeError::KeyNotFound)?;
+        let (_, event) = self.root.take().unwrap().delete(key);
+        self.root = event.into_option();
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        self.root
+            .as_ref()
+            .and_then(|root| AugmentedAvl::get(root, key))
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    /// Combines the values whose keys fall into `range`, in key order, in O(log n).
+    pub fn aggregate<R: RangeBounds<K>>(&self, range: R) -> A {
+        self.root
+            .as_ref()
+            .map_or_else(A::empty, |root| root.aggregate(range))
+    }
+
+    /// Recomputes the summaries for `key` after its value has been edited through its
+    /// cell. Returns whether the key was found.
+    pub fn refresh(&mut self, key: K) -> bool {
+        self.root.as_ref().is_some_and(|root| root.refresh(key))
+    }
+}
+
+impl<K: Ord + Copy + Debug, V, A: Aggregate<V>> AvlMap<K, V, A> {
+    /// Verifies the tree; an empty map is always valid.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        match self.root.as_ref() {
+            Some(root) => root.check_invariants(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K: Ord + Copy, V, A: Aggregate<V>> Default for AvlMap<K, V, A> {
+    fn default() -> Self {
+        Self::with_aggregate()
+    }
+}
+
+map_api!([K: Ord + Copy, V, A: Aggregate<V>] AvlMap<K, V, A>, Iter<V, K, State, A>);
@@ -0,0 +1,209 @@
+use std::{cell::RefCell, cmp, rc::Rc};
+
+use crate::trees::bintree::{aggregate::Aggregate, trunk::Trunk, BinTree};
+
+use super::{AugmentedAvl, Event, Pair, State};
+
+// Both halves of a split, each followed by its height.
+type Halves<T, K, A> = (
+    Option<BinTree<T, K, State, A>>,
+    usize,
+    Option<Pair<T, K>>,
+    Option<BinTree<T, K, State, A>>,
+    usize,
+);
+
+impl<T, K: Ord + Copy, A: Aggregate<T>> BinTree<T, K, State, A> {
+    fn leaf(key: K, value: Rc<RefCell<T>>) -> Self {
+        let aggregate = A::of(&value.borrow());
+        Self {
+            t: Rc::new(RefCell::new(Trunk {
+                key,
+                value,
+                left: None,
+                right: None,
+                state: State::Balanced,
+                size: 1,
+                aggregate,
+            })),
+        }
+    }
+
+    // Follows the taller side down to a leaf.
+    fn height(&self) -> usize {
+        let mut height = 0;
+        let mut node = Some(Self { t: self.t.clone() });
+        while let Some(n) = node {
+            height += 1;
+            let trunk = n.t.borrow();
+            node = match trunk.state {
+                State::LeftSided => trunk.left.as_ref(),
+                State::Balanced | State::RightSided => trunk.right.as_ref(),
+            }
+            .map(|c| Self { t: c.t.clone() });
+        }
+        height
+    }
+
+    // The heights of the left and right branches, given the height of self.
+    fn branch_heights(&self, height: usize) -> (usize, usize) {
+        match self.t.borrow().state {
+            State::LeftSided => (height - 1, height - 2),
+            State::Balanced => (height - 1, height - 1),
+            State::RightSided => (height - 2, height - 1),
+        }
+    }
+
+    // Hangs two branches of similar heights below the detached node self.
+    fn hang(self, left: Option<Self>, hl: usize, right: Option<Self>, hr: usize) -> Self {
+        {
+            let mut trunk = self.t.borrow_mut();
+            trunk.left = left;
+            trunk.right = right;
+            trunk.state = match hl.cmp(&hr) {
+                cmp::Ordering::Greater => State::LeftSided,
+                cmp::Ordering::Equal => State::Balanced,
+                cmp::Ordering::Less => State::RightSided,
+            };
+            trunk.update();
+        }
+        self
+    }
+
+    // Joins left, the detached node and right, returning the tree and its height.
+    fn join_at(
+        left: Option<Self>,
+        hl: usize,
+        node: Self,
+        right: Option<Self>,
+        hr: usize,
+    ) -> (Self, usize) {
+        let (event, height) = if hr + 1 < hl {
+            (left.unwrap().join_right(hl, node, right, hr), hl)
+        } else if hl + 1 < hr {
+            (right.unwrap().join_left(hr, node, left, hl), hr)
+        } else {
+            return (node.hang(left, hl, right, hr), cmp::max(hl, hr) + 1);
+        };
+        match event {
+            Event::Grown(t) => (t, height + 1),
+            Event::None(t) => (t, height),
+            Event::Shrunk(_) => panic!("Joining cannot shrink a tree."),
+        }
+    }
+
+    // Descends the right spine until the branch is at most one level taller than right.
+    fn join_right(
+        self,
+        height: usize,
+        node: Self,
+        right: Option<Self>,
+        hr: usize,
+    ) -> Event<T, K, A> {
+        let (_, hc) = self.branch_heights(height);
+        let branch = self.t.borrow_mut().right.take();
+        let event = if hc <= hr + 1 {
+            Event::Grown(node.hang(branch, hc, right, hr))
+        } else {
+            branch.unwrap().join_right(hc, node, right, hr)
+        };
+        self.remerge_right_branch(event)
+    }
+
+    fn join_left(self, height: usize, node: Self, left: Option<Self>, hl: usize) -> Event<T, K, A> {
+        let (hc, _) = self.branch_heights(height);
+        let branch = self.t.borrow_mut().left.take();
+        let event = if hc <= hl + 1 {
+            Event::Grown(node.hang(left, hl, branch, hc))
+        } else {
+            branch.unwrap().join_left(hc, node, left, hl)
+        };
+        self.remerge_left_branch(event)
+    }
+
+    fn split_at(self, height: usize, key: K) -> Halves<T, K, A> {
+        let (hl, hr) = self.branch_heights(height);
+        let (left, right, here) = {
+            let mut trunk = self.t.borrow_mut();
+            (trunk.left.take(), trunk.right.take(), trunk.key)
+        };
+        if key < here {
+            let (ll, hll, found, lr, hlr) = match left {
+                Some(left) => left.split_at(hl, key),
+                None => (None, 0, None, None, 0),
+            };
+            let (joined, hj) = Self::join_at(lr, hlr, self, right, hr);
+            (ll, hll, found, Some(joined), hj)
+        } else if here < key {
+            let (rl, hrl, found, rr, hrr) = match right {
+                Some(right) => right.split_at(hr, key),
+                None => (None, 0, None, None, 0),
+            };
+            let (joined, hj) = Self::join_at(left, hl, self, rl, hrl);
+            (Some(joined), hj, found, rr, hrr)
+        } else {
+            let value = self.t.borrow().value.clone();
+            (left, hl, Some((key, value)), right, hr)
+        }
+    }
+
+    /// Splits the tree into the pairs below `key`, the pair at `key` and those above it
+    /// in O(log n).
+    pub fn split(self, key: K) -> (Option<Self>, Option<Pair<T, K>>, Option<Self>) {
+        let height = self.height();
+        let (left, _, found, right, _) = self.split_at(height, key);
+        (left, found, right)
+    }
+
+    /// Builds the tree holding `left`, the pair `(key, value)` and `right` in O(log n).
+    /// All keys in `left` must be below `key` and all keys in `right` above it.
+    pub fn join(left: Option<Self>, key: K, value: T, right: Option<Self>) -> Self {
+        assert!(
+            left.as_ref().is_none_or(|l| l.last().0 < key)
+                && right.as_ref().is_none_or(|r| key < r.first().0),
+            "The keys of the trees to join are not in order."
+        );
+        let hl = left.as_ref().map_or(0, |l| l.height());
+        let hr = right.as_ref().map_or(0, |r| r.height());
+        Self::join_at(left, hl, Self::new(key, value), right, hr).0
+    }
+
+    /// Moves all pairs of `other` into the tree; on equal keys the values of `other` win.
+    /// Runs in O(log n) when the key ranges do not overlap.
+    pub fn append(self, other: Self) -> Self {
+        if self.last().0 < other.first().0 {
+            let ((key, value), event) = other.pop_first();
+            let rest = event.into_option();
+            let (hl, hr) = (self.height(), rest.as_ref().map_or(0, |r| r.height()));
+            Self::join_at(Some(self), hl, Self::leaf(key, value), rest, hr).0
+        } else if other.last().0 < self.first().0 {
+            let ((key, value), event) = other.pop_last();
+            let rest = event.into_option();
+            let (hl, hr) = (rest.as_ref().map_or(0, |r| r.height()), self.height());
+            Self::join_at(rest, hl, Self::leaf(key, value), Some(self), hr).0
+        } else {
+            Self::union(Some(self), Some(other)).unwrap()
+        }
+    }
+
+    fn union(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        let (a, b) = match (a, b) {
+            (None, b) => return b,
+            (a, None) => return a,
+            (Some(a), Some(b)) => (a, b),
+        };
+        let (al, ar, key) = {
+            let mut trunk = a.t.borrow_mut();
+            (trunk.left.take(), trunk.right.take(), trunk.key)
+        };
+        let (bl, found, br) = b.split(key);
+        if let Some((_, value)) = found {
+            a.t.borrow_mut().value = value;
+        }
+        let left = Self::union(al, bl);
+        let right = Self::union(ar, br);
+        let hl = left.as_ref().map_or(0, |l| l.height());
+        let hr = right.as_ref().map_or(0, |r| r.height());
+        Some(Self::join_at(left, hl, a, right, hr).0)
+    }
+}
@@ -1,27 +1,27 @@
-use crate::trees::bintree::BinTree;
+use crate::trees::bintree::{aggregate::Aggregate, BinTree};
 
 use super::{State, Event};
 
-impl<T, K: Ord + Copy> BinTree<T, K, State> {
-    pub(super) fn rotate_to_left(self: Self) -> Event<T, K> {
+impl<T, K: Ord + Copy, A: Aggregate<T>> BinTree<T, K, State, A> {
+    pub(super) fn rotate_to_left(self) -> Event<T, K, A> {
         let state_of_right = self.t.borrow().right.as_ref().unwrap().t.borrow().state;
         match state_of_right {
-            State::RightSided => self.rotate_to_left_once(),
+            // The balanced case only occurs on deletion.
+            State::RightSided | State::Balanced => self.rotate_to_left_once(),
             State::LeftSided => self.rotate_to_left_twice(),
-            State::Balanced => panic!("Rotation should not be necessary in this case."),
         }
     }
 
-    pub(super) fn rotate_to_right(self: Self) -> Event<T, K> {
+    pub(super) fn rotate_to_right(self) -> Event<T, K, A> {
         let state_of_left = self.t.borrow().left.as_ref().unwrap().t.borrow().state;
         match state_of_left {
-            State::LeftSided => self.rotate_to_right_once(),
+            // The balanced case only occurs on deletion.
+            State::LeftSided | State::Balanced => self.rotate_to_right_once(),
             State::RightSided => self.rotate_to_right_twice(),
-            State::Balanced => panic!("Rotation should not be necessary in this case."),
         }
     }
 
-    fn rotate_to_right_once(self: Self) -> Event<T, K> {
+    fn rotate_to_right_once(self) -> Event<T, K, A> {
         // Take necessary branches
         let left = self.t.borrow_mut().left.take().unwrap();
         let right_of_left = left.t.borrow_mut().right.take();
@@ -32,19 +32,22 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
         match state {
             State::LeftSided => {
                 self.t.borrow_mut().state = State::Balanced;
+                left.t.borrow_mut().state = State::Balanced;
                 shrunk = true;
             }
             State::Balanced => {
                 self.t.borrow_mut().state = State::LeftSided;
+                left.t.borrow_mut().state = State::RightSided;
                 shrunk = false;
             }
             State::RightSided => panic!("Single rotation cannot be applicable in this case."),
         };
-        left.t.borrow_mut().state = State::Balanced;
 
         // Here we move
         self.t.borrow_mut().left = right_of_left;
+        self.t.borrow_mut().update();
         left.t.borrow_mut().right = Some(self);
+        left.t.borrow_mut().update();
         if shrunk {
             Event::Shrunk(Some(left))
         } else {
@@ -52,7 +55,7 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
         }
     }
 
-    fn rotate_to_right_twice(self: Self) -> Event<T, K> {
+    fn rotate_to_right_twice(self) -> Event<T, K, A> {
         // Take necessary branches
         let left = self.t.borrow_mut().left.take().unwrap();
         let right_of_left = left.t.borrow_mut().right.take().unwrap();
@@ -71,22 +74,26 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
                 left.t.borrow_mut().state = State::Balanced;
             }
             State::Balanced => {
-                panic!("Not supposed to arrive here.");
+                self.t.borrow_mut().state = State::Balanced;
+                left.t.borrow_mut().state = State::Balanced;
             }
         };
         right_of_left.t.borrow_mut().state = State::Balanced;
 
         // Here we move
         self.t.borrow_mut().left = right_of_right_of_left;
+        self.t.borrow_mut().update();
         left.t.borrow_mut().right = left_of_right_of_left;
+        left.t.borrow_mut().update();
         right_of_left.t.borrow_mut().right = Some(self);
         right_of_left.t.borrow_mut().left = Some(left);
+        right_of_left.t.borrow_mut().update();
 
         // Double rotation always makes trees contract.
         Event::Shrunk(Some(right_of_left))
     }
 
-    fn rotate_to_left_once(self: Self) -> Event<T, K> {
+    fn rotate_to_left_once(self) -> Event<T, K, A> {
         // Take necessary branches
         let right = self.t.borrow_mut().right.take().unwrap();
         let left_of_right = right.t.borrow_mut().left.take();
@@ -97,19 +104,22 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
         match state {
             State::RightSided => {
                 self.t.borrow_mut().state = State::Balanced;
+                right.t.borrow_mut().state = State::Balanced;
                 shrunk = true;
             }
             State::Balanced => {
                 self.t.borrow_mut().state = State::RightSided;
+                right.t.borrow_mut().state = State::LeftSided;
                 shrunk = false;
             }
             State::LeftSided => panic!("Single rotation cannot be applicable in this case."),
         };
-        right.t.borrow_mut().state = State::Balanced;
 
         // Here we move
         self.t.borrow_mut().right = left_of_right;
+        self.t.borrow_mut().update();
         right.t.borrow_mut().left = Some(self);
+        right.t.borrow_mut().update();
         if shrunk {
             Event::Shrunk(Some(right))
         } else {
@@ -117,7 +127,7 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
         }
     }
 
-    fn rotate_to_left_twice(self: Self) -> Event<T, K> {
+    fn rotate_to_left_twice(self) -> Event<T, K, A> {
         // Take necessary branches
         let right = self.t.borrow_mut().right.take().unwrap();
         let left_of_right = right.t.borrow_mut().left.take().unwrap();
@@ -136,16 +146,20 @@ impl<T, K: Ord + Copy> BinTree<T, K, State> {
                 right.t.borrow_mut().state = State::RightSided;
             }
             State::Balanced => {
-                panic!("Not supposed to arrive here.");
+                self.t.borrow_mut().state = State::Balanced;
+                right.t.borrow_mut().state = State::Balanced;
             }
         };
         left_of_right.t.borrow_mut().state = State::Balanced;
 
         // Here we move
         self.t.borrow_mut().right = left_of_left_of_right;
+        self.t.borrow_mut().update();
         right.t.borrow_mut().left = right_of_left_of_right;
+        right.t.borrow_mut().update();
         left_of_right.t.borrow_mut().right = Some(right);
         left_of_right.t.borrow_mut().left = Some(self);
+        left_of_right.t.borrow_mut().update();
 
         // Double rotation always makes trees contract.
         Event::Shrunk(Some(left_of_right))
@@ -0,0 +1,78 @@
+use std::{cell::RefCell, cmp, rc::Rc};
+
+use crate::{error::TreeError, trees::check_sorted};
+
+use super::{aggregate::Aggregate, avl::State, trunk::Trunk, BinTree};
+
+// The height of a perfectly balanced tree of n nodes.
+fn height(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as usize
+}
+
+impl<T, K: Ord + Copy, S, A: Aggregate<T>> BinTree<T, K, S, A> {
+    // Builds a perfectly balanced tree from the next n pairs, consumed in order.
+    // The state of each node is derived from the heights of its branches.
+    fn build<I, F>(n: usize, items: &mut I, state: &F) -> Option<Self>
+    where
+        I: Iterator<Item = (K, T)>,
+        F: Fn(usize, usize) -> S,
+    {
+        if n == 0 {
+            return None;
+        }
+        let nl = (n - 1) / 2;
+        let nr = n - 1 - nl;
+        let left = Self::build(nl, items, state);
+        let (key, value) = items.next().expect("Fewer pairs than counted.");
+        let right = Self::build(nr, items, state);
+        let node = Self {
+            t: Rc::new(RefCell::new(Trunk {
+                key,
+                value: Rc::new(RefCell::new(value)),
+                left,
+                right,
+                state: state(height(nl), height(nr)),
+                size: n,
+                aggregate: A::empty(),
+            })),
+        };
+        node.t.borrow_mut().update();
+        Some(node)
+    }
+}
+
+impl<T, K: Ord + Copy, A: Aggregate<T>> BinTree<T, K, State, A> {
+    /// Builds a perfectly balanced AVL tree from pairs in ascending key order in O(n).
+    /// Returns `None` for empty input.
+    pub fn from_sorted_iter<I>(iter: I) -> Result<Option<Self>, TreeError>
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        let items: Vec<(K, T)> = iter.into_iter().collect();
+        check_sorted(&items)?;
+        let n = items.len();
+        Ok(Self::build(
+            n,
+            &mut items.into_iter(),
+            &|hl, hr| match hl.cmp(&hr) {
+                cmp::Ordering::Greater => State::LeftSided,
+                cmp::Ordering::Equal => State::Balanced,
+                cmp::Ordering::Less => State::RightSided,
+            },
+        ))
+    }
+}
+
+impl<T, K: Ord + Copy> BinTree<T, K, ()> {
+    /// Builds a perfectly balanced plain tree from pairs in ascending key order in O(n).
+    /// Returns `None` for empty input.
+    pub fn from_sorted_iter<I>(iter: I) -> Result<Option<Self>, TreeError>
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        let items: Vec<(K, T)> = iter.into_iter().collect();
+        check_sorted(&items)?;
+        let n = items.len();
+        Ok(Self::build(n, &mut items.into_iter(), &|_, _| ()))
+    }
+}
@@ -0,0 +1,125 @@
+use std::{cmp, fmt::Debug};
+
+use crate::error::TreeError;
+
+use super::{avl::State, BinTree};
+
+// What a subtree looks like from its parent: smallest and largest key, size and height.
+struct Summary<K> {
+    min: K,
+    max: K,
+    size: usize,
+    height: usize,
+}
+
+// The summaries of the left and right branch of a node, once they are known.
+type Branches<K> = (Option<Summary<K>>, Option<Summary<K>>);
+
+fn corrupt(report: String) -> TreeError {
+    TreeError::CorruptStructure(report)
+}
+
+impl<T, K: Ord + Copy + Debug, S, A> BinTree<T, K, S, A> {
+    // Checks ordering and subtree sizes bottom-up, handing the state of every node and
+    // the heights of its branches to `check_state`. Walks with an explicit stack, like
+    // the iterator, so that degenerate plain trees do not overflow the call stack.
+    pub(super) fn check_with<F>(&self, check_state: F) -> Result<(), TreeError>
+    where
+        F: Fn(K, &S, usize, usize) -> Result<(), TreeError>,
+    {
+        // Parents are listed before their children, so walking the list backwards
+        // visits every subtree before the node it hangs from.
+        let mut nodes: Vec<(Self, Option<(usize, bool)>)> = Vec::new();
+        let mut stack = vec![(Self { t: self.t.clone() }, None)];
+        while let Some((node, parent)) = stack.pop() {
+            let idx = nodes.len();
+            {
+                let trunk = node.t.borrow();
+                if let Some(r) = trunk.right.as_ref() {
+                    stack.push((Self { t: r.t.clone() }, Some((idx, false))));
+                }
+                if let Some(l) = trunk.left.as_ref() {
+                    stack.push((Self { t: l.t.clone() }, Some((idx, true))));
+                }
+            }
+            nodes.push((node, parent));
+        }
+        let mut branches: Vec<Branches<K>> = (0..nodes.len()).map(|_| (None, None)).collect();
+        for (idx, (node, parent)) in nodes.iter().enumerate().rev() {
+            let trunk = node.t.borrow();
+            let (left, right) = (branches[idx].0.take(), branches[idx].1.take());
+            if let Some(l) = left.as_ref().filter(|l| trunk.key <= l.max) {
+                return Err(corrupt(format!(
+                    "key {:?} in the left branch of {:?}",
+                    l.max, trunk.key
+                )));
+            }
+            if let Some(r) = right.as_ref().filter(|r| r.min <= trunk.key) {
+                return Err(corrupt(format!(
+                    "key {:?} in the right branch of {:?}",
+                    r.min, trunk.key
+                )));
+            }
+            let (hl, hr) = (
+                left.as_ref().map_or(0, |l| l.height),
+                right.as_ref().map_or(0, |r| r.height),
+            );
+            check_state(trunk.key, &trunk.state, hl, hr)?;
+            let size =
+                1 + left.as_ref().map_or(0, |l| l.size) + right.as_ref().map_or(0, |r| r.size);
+            if trunk.size != size {
+                return Err(corrupt(format!(
+                    "node {:?} records size {} but holds {} nodes",
+                    trunk.key, trunk.size, size
+                )));
+            }
+            let summary = Some(Summary {
+                min: left.as_ref().map_or(trunk.key, |l| l.min),
+                max: right.as_ref().map_or(trunk.key, |r| r.max),
+                size,
+                height: 1 + cmp::max(hl, hr),
+            });
+            match parent {
+                Some((p, true)) => branches[*p].0 = summary,
+                Some((p, false)) => branches[*p].1 = summary,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T, K: Ord + Copy + Debug, A> BinTree<T, K, State, A> {
+    /// Verifies the key order, the subtree sizes and that every `State` matches the
+    /// actual heights of the branches, reporting the first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        self.check_with(|key, state, hl, hr| {
+            let expected = match hl as isize - hr as isize {
+                1 => State::LeftSided,
+                0 => State::Balanced,
+                -1 => State::RightSided,
+                _ => {
+                    return Err(corrupt(format!(
+                        "node {:?} has branches of heights {} and {}",
+                        key, hl, hr
+                    )))
+                }
+            };
+            if *state == expected {
+                Ok(())
+            } else {
+                Err(corrupt(format!(
+                    "node {:?} is {:?} but has branches of heights {} and {}",
+                    key, state, hl, hr
+                )))
+            }
+        })
+    }
+}
+
+impl<T, K: Ord + Copy + Debug> BinTree<T, K, ()> {
+    /// Verifies the key order and the subtree sizes, reporting the first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        self.check_with(|_, _, _, _| Ok(()))
+    }
+}
@@ -0,0 +1,90 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{aggregate::Aggregate, avl::State, BinTree};
+
+// The insert of the tree an entry stands in, handing back the new root.
+type Insert<T, K, S, A> = fn(BinTree<T, K, S, A>, K, T) -> BinTree<T, K, S, A>;
+
+/// A view into a single key of a tree, obtained from `Avl::entry` or `RedBlack::entry`.
+///
+/// Since inserting may rotate the root away, the entry owns the root and
+/// hands it back together with the value once it is resolved.
+pub enum Entry<T, K: Ord + Copy, A = (), S = State> {
+    Occupied(OccupiedEntry<T, K, A, S>),
+    Vacant(VacantEntry<T, K, A, S>),
+}
+
+pub struct OccupiedEntry<T, K: Ord + Copy, A = (), S = State> {
+    pub(super) root: BinTree<T, K, S, A>,
+    pub(super) key: K,
+    pub(super) value: Rc<RefCell<T>>,
+}
+
+pub struct VacantEntry<T, K: Ord + Copy, A = (), S = State> {
+    pub(super) root: BinTree<T, K, S, A>,
+    pub(super) key: K,
+    pub(super) insert: Insert<T, K, S, A>,
+}
+
+impl<T, K: Ord + Copy, A: Aggregate<T>, S> Entry<T, K, A, S> {
+    pub(super) fn new(root: BinTree<T, K, S, A>, key: K, insert: Insert<T, K, S, A>) -> Self {
+        match root.find_node(key) {
+            Some(node) => {
+                let value = node.t.borrow().value.clone();
+                Entry::Occupied(OccupiedEntry { root, key, value })
+            }
+            None => Entry::Vacant(VacantEntry { root, key, insert }),
+        }
+    }
+
+    pub fn key(&self) -> K {
+        match self {
+            Entry::Occupied(e) => e.key,
+            Entry::Vacant(e) => e.key,
+        }
+    }
+
+    pub fn or_insert(self, default: T) -> (BinTree<T, K, S, A>, Rc<RefCell<T>>) {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(
+        self,
+        default: F,
+    ) -> (BinTree<T, K, S, A>, Rc<RefCell<T>>) {
+        match self {
+            Entry::Occupied(e) => (e.root, e.value),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        if let Entry::Occupied(ref e) = self {
+            f(&mut e.value.borrow_mut());
+            e.root.update_path(e.key);
+        }
+        self
+    }
+}
+
+impl<T, K: Ord + Copy, A, S> OccupiedEntry<T, K, A, S> {
+    pub fn get(&self) -> Rc<RefCell<T>> {
+        self.value.clone()
+    }
+
+    pub fn into_root(self) -> BinTree<T, K, S, A> {
+        self.root
+    }
+}
+
+impl<T, K: Ord + Copy, A, S> VacantEntry<T, K, A, S> {
+    pub fn insert(self, value: T) -> (BinTree<T, K, S, A>, Rc<RefCell<T>>) {
+        let root = (self.insert)(self.root, self.key, value);
+        let value = root.find_node(self.key).unwrap().t.borrow().value.clone();
+        (root, value)
+    }
+
+    pub fn into_root(self) -> BinTree<T, K, S, A> {
+        self.root
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    ops::{Bound, Range},
+    rc::Rc,
+};
+
+use crate::error::TreeError;
+
+use super::{
+    aggregate::Aggregate,
+    avl::{AugmentedAvl, State},
+    iter::below,
+    BinTree,
+};
+
+// The ends and values of the intervals sharing a start, in insertion order.
+type Bucket<K, V> = Vec<(K, Rc<RefCell<V>>)>;
+
+type Node<K, V> = BinTree<Bucket<K, V>, K, State, MaxEnd<K>>;
+
+// The largest end of the intervals in a subtree, which lets queries skip the subtrees
+// that end before the window they look at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MaxEnd<K>(Option<K>);
+
+impl<K: Ord + Copy, V> Aggregate<Bucket<K, V>> for MaxEnd<K> {
+    fn empty() -> Self {
+        Self(None)
+    }
+
+    fn of(bucket: &Bucket<K, V>) -> Self {
+        Self(bucket.iter().map(|(end, _)| *end).max())
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        Self(self.0.max(upper.0))
+    }
+}
+
+/// What `overlapping` looks for: a point, or a half-open range of points.
+pub trait Window<K> {
+    /// The point intervals must end after, and the bound their starts must lie within.
+    /// `None` if nothing can overlap the window.
+    fn bounds(self) -> Option<(K, Bound<K>)>;
+}
+
+impl<K: Ord + Copy> Window<K> for K {
+    fn bounds(self) -> Option<(K, Bound<K>)> {
+        Some((self, Bound::Included(self)))
+    }
+}
+
+impl<K: Ord + Copy> Window<K> for Range<K> {
+    fn bounds(self) -> Option<(K, Bound<K>)> {
+        (self.start < self.end).then_some((self.start, Bound::Excluded(self.end)))
+    }
+}
+
+/// A set of half-open intervals `[start, end)` with values, on an AVL tree keyed by start.
+///
+/// Each node keeps the largest end below it, so finding the intervals that overlap a
+/// window costs O(log n) plus O(log n) per interval found.
+pub struct IntervalTree<K: Ord + Copy, V> {
+    root: Option<Node<K, V>>,
+    len: usize,
+}
+
+impl<K: Ord + Copy, V> IntervalTree<K, V> {
+    pub fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    /// Adds the interval `range` with `value`. Equal intervals may be added repeatedly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    pub fn insert(&mut self, range: Range<K>, value: V) {
+        assert!(range.start < range.end, "An interval must not be empty.");
+        let entry = (range.end, Rc::new(RefCell::new(value)));
+        self.len += 1;
+        match self.root.take() {
+            None => self.root = Some(BinTree::new(range.start, vec![entry])),
+            Some(root) => match AugmentedAvl::get(&root, &range.start) {
+                Some(bucket) => {
+                    bucket.borrow_mut().push(entry);
+                    root.refresh(range.start);
+                    self.root = Some(root);
+                }
+                None => self.root = root.insert(range.start, vec![entry]).into_option(),
+            },
+        }
+    }
+
+    /// Removes the earliest added interval equal to `range` and returns its value.
+    pub fn remove(&mut self, range: Range<K>) -> Result<Rc<RefCell<V>>, TreeError> {
+        let root = self.root.as_ref().ok_or(TreeError::KeyNotFound)?;
+        let bucket = AugmentedAvl::get(root, &range.start).ok_or(TreeError::KeyNotFound)?;
+        let pos = bucket
+            .borrow()
+            .iter()
+            .position(|(end, _)| *end == range.end)
+            .ok_or(TreeError::KeyNotFound)?;
+        let (_, value) = bucket.borrow_mut().remove(pos);
+        if bucket.borrow().is_empty() {
+            let (_, event) = self.root.take().unwrap().delete(range.start);
+            self.root = event.into_option();
+        } else {
+            root.refresh(range.start);
+        }
+        self.len -= 1;
+        Ok(value)
+    }
+
+    /// Iterates over the intervals overlapping `window`, ordered by start, e.g.
+    /// `tree.overlapping(5)` or `tree.overlapping(3..7)`.
+    pub fn overlapping<W: Window<K>>(&self, window: W) -> Overlapping<K, V> {
+        let mut it = Overlapping {
+            stack: Vec::new(),
+            found: Vec::new(),
+            after: None,
+            starts: Bound::Unbounded,
+        };
+        if let Some((after, starts)) = window.bounds() {
+            it.after = Some(after);
+            it.starts = starts;
+            it.seed(self.root.as_ref().map(|r| BinTree { t: r.t.clone() }));
+        }
+        it
+    }
+
+    /// The number of intervals.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+}
+
+impl<K: Ord + Copy + Debug, V> IntervalTree<K, V> {
+    /// Verifies the tree, the largest ends kept in it and that no interval is empty.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        let Some(root) = self.root.as_ref() else {
+            return Ok(());
+        };
+        root.check_invariants()?;
+        root.check_aggregates()?;
+        let mut len = 0;
+        for (start, bucket) in root.iter() {
+            let bucket = bucket.borrow();
+            if let Some((end, _)) = bucket.iter().find(|(end, _)| *end <= start) {
+                return Err(TreeError::CorruptStructure(format!(
+                    "empty interval {:?}..{:?}",
+                    start, end
+                )));
+            }
+            if bucket.is_empty() {
+                return Err(TreeError::CorruptStructure(format!(
+                    "no intervals start at {:?}",
+                    start
+                )));
+            }
+            len += bucket.len();
+        }
+        if len != self.len {
+            return Err(TreeError::CorruptStructure(format!(
+                "{} intervals counted as {}",
+                len, self.len
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Ord + Copy, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Copy, V> FromIterator<(Range<K>, V)> for IntervalTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        for (range, value) in iter {
+            tree.insert(range, value);
+        }
+        tree
+    }
+}
+
+/// Iterator over the intervals overlapping a window, from `IntervalTree::overlapping`.
+///
+/// It walks the tree in order with an explicit stack, leaving out the subtrees that
+/// end too early and those that start too late.
+pub struct Overlapping<K: Ord + Copy, V> {
+    // Nodes starting early enough, whose right branches are still to be seen.
+    stack: Vec<Node<K, V>>,
+    // Matches from the current node, reversed.
+    found: Vec<(Range<K>, Rc<RefCell<V>>)>,
+    after: Option<K>,
+    starts: Bound<K>,
+}
+
+impl<K: Ord + Copy, V> Overlapping<K, V> {
+    fn ends_after(&self, end: Option<K>) -> bool {
+        end.is_some_and(|end| self.after.is_some_and(|after| after < end))
+    }
+
+    fn starts_within(&self, start: K) -> bool {
+        below(&start, &self.starts)
+    }
+
+    fn seed(&mut self, mut node: Option<Node<K, V>>) {
+        while let Some(n) = node {
+            let trunk = n.t.borrow();
+            if !self.ends_after(trunk.aggregate.0) {
+                break;
+            }
+            node = trunk.left.as_ref().map(|l| BinTree { t: l.t.clone() });
+            // Everything to the right starts later still.
+            if self.starts_within(trunk.key) {
+                drop(trunk);
+                self.stack.push(n);
+            }
+        }
+    }
+}
+
+impl<K: Ord + Copy, V> Iterator for Overlapping<K, V> {
+    type Item = (Range<K>, Rc<RefCell<V>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.found.pop() {
+                return Some(found);
+            }
+            let node = self.stack.pop()?;
+            let trunk = node.t.borrow();
+            let start = trunk.key;
+            self.found = trunk
+                .value
+                .borrow()
+                .iter()
+                .rev()
+                .filter(|(end, _)| self.ends_after(Some(*end)))
+                .map(|(end, value)| (start..*end, value.clone()))
+                .collect();
+            let right = trunk.right.as_ref().map(|r| BinTree { t: r.t.clone() });
+            drop(trunk);
+            self.seed(right);
+        }
+    }
+}
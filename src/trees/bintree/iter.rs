@@ -0,0 +1,229 @@
+use std::{
+    cell::RefCell,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+};
+
+use super::BinTree;
+
+/// In-order iterator over a binary tree.
+///
+/// It walks the tree with two explicit stacks (one per direction) instead of
+/// recursion, so deep trees do not overflow the call stack. Nodes are held by
+/// shared handles, which is why values come out as `Rc<RefCell<T>>`: the nodes
+/// sit in `RefCell`s and the trees change through `&self`, so a `&T` into a node
+/// could not be kept past the next step.
+pub struct Iter<T, K: Ord + Copy, S, A = ()> {
+    front: Vec<BinTree<T, K, S, A>>,
+    back: Vec<BinTree<T, K, S, A>>,
+    lower: Bound<K>,
+    upper: Bound<K>,
+    done: bool,
+}
+
+pub struct Keys<T, K: Ord + Copy, S, A = ()>(Iter<T, K, S, A>);
+
+pub struct Values<T, K: Ord + Copy, S, A = ()>(Iter<T, K, S, A>);
+
+fn left_of<T, K: Ord + Copy, S, A>(node: &BinTree<T, K, S, A>) -> Option<BinTree<T, K, S, A>> {
+    node.t
+        .borrow()
+        .left
+        .as_ref()
+        .map(|l| BinTree { t: l.t.clone() })
+}
+
+fn right_of<T, K: Ord + Copy, S, A>(node: &BinTree<T, K, S, A>) -> Option<BinTree<T, K, S, A>> {
+    node.t
+        .borrow()
+        .right
+        .as_ref()
+        .map(|r| BinTree { t: r.t.clone() })
+}
+
+// Whether `key` satisfies `bound` as the lower end of a range. Shared with the range
+// descents of the aggregates and the interval tree.
+pub(super) fn above<K: Ord>(key: &K, bound: &Bound<K>) -> bool {
+    match bound {
+        Bound::Included(b) => b <= key,
+        Bound::Excluded(b) => b < key,
+        Bound::Unbounded => true,
+    }
+}
+
+// Whether `key` satisfies `bound` as the upper end of a range.
+pub(super) fn below<K: Ord>(key: &K, bound: &Bound<K>) -> bool {
+    match bound {
+        Bound::Included(b) => key <= b,
+        Bound::Excluded(b) => key < b,
+        Bound::Unbounded => true,
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> Iter<T, K, S, A> {
+    pub(super) fn new(
+        root: Option<&BinTree<T, K, S, A>>,
+        lower: Bound<K>,
+        upper: Bound<K>,
+    ) -> Self {
+        let mut it = Self {
+            front: Vec::new(),
+            back: Vec::new(),
+            lower,
+            upper,
+            done: false,
+        };
+        if let Some(root) = root {
+            it.seed_front(BinTree { t: root.t.clone() });
+            it.seed_back(BinTree { t: root.t.clone() });
+        }
+        it
+    }
+
+    // Pushes the path to the smallest key satisfying the lower bound.
+    fn seed_front(&mut self, mut node: BinTree<T, K, S, A>) {
+        loop {
+            let next = if above(&node.t.borrow().key, &self.lower) {
+                left_of(&node)
+            } else {
+                right_of(&node)
+            };
+            if above(&node.t.borrow().key, &self.lower) {
+                self.front.push(node);
+            }
+            match next {
+                Some(next) => node = next,
+                None => break,
+            }
+        }
+    }
+
+    // Pushes the path to the largest key satisfying the upper bound.
+    fn seed_back(&mut self, mut node: BinTree<T, K, S, A>) {
+        loop {
+            let next = if below(&node.t.borrow().key, &self.upper) {
+                right_of(&node)
+            } else {
+                left_of(&node)
+            };
+            if below(&node.t.borrow().key, &self.upper) {
+                self.back.push(node);
+            }
+            match next {
+                Some(next) => node = next,
+                None => break,
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.done = true;
+        self.front.clear();
+        self.back.clear();
+        None
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> Iterator for Iter<T, K, S, A> {
+    type Item = (K, Rc<RefCell<T>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let node = match self.front.pop() {
+            Some(node) => node,
+            None => return self.finish(),
+        };
+        let key = node.t.borrow().key;
+        if !below(&key, &self.upper) {
+            return self.finish();
+        }
+        // Everything on the right hand side is above the lower bound.
+        let mut next = right_of(&node);
+        while let Some(n) = next {
+            next = left_of(&n);
+            self.front.push(n);
+        }
+        // Stop the other end from yielding this key again.
+        self.lower = Bound::Excluded(key);
+        let value = node.t.borrow().value.clone();
+        Some((key, value))
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> DoubleEndedIterator for Iter<T, K, S, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let node = match self.back.pop() {
+            Some(node) => node,
+            None => return self.finish(),
+        };
+        let key = node.t.borrow().key;
+        if !above(&key, &self.lower) {
+            return self.finish();
+        }
+        let mut next = left_of(&node);
+        while let Some(n) = next {
+            next = right_of(&n);
+            self.back.push(n);
+        }
+        self.upper = Bound::Excluded(key);
+        let value = node.t.borrow().value.clone();
+        Some((key, value))
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> Iterator for Keys<T, K, S, A> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.0.next().map(|(k, _)| k)
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> DoubleEndedIterator for Keys<T, K, S, A> {
+    fn next_back(&mut self) -> Option<K> {
+        self.0.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> Iterator for Values<T, K, S, A> {
+    type Item = Rc<RefCell<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, v)| v)
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> DoubleEndedIterator for Values<T, K, S, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<T, K: Ord + Copy, S, A> BinTree<T, K, S, A> {
+    /// Iterates over `(key, value)` pairs in ascending key order.
+    pub fn iter(&self) -> Iter<T, K, S, A> {
+        Iter::new(Some(self), Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn keys(&self) -> Keys<T, K, S, A> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<T, K, S, A> {
+        Values(self.iter())
+    }
+
+    /// Iterates over the pairs whose keys fall into `range`, e.g. `tree.range(3..7)`.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<T, K, S, A> {
+        Iter::new(
+            Some(self),
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+}
@@ -0,0 +1,157 @@
+// The API that all maps over an optional `BinTree` root share. A map brings its own
+// `insert_new` for keys it does not hold yet, `remove` and `get`, a `dup` field with
+// its duplicates policy, and `tree`, which hands out a handle to its root; the macro
+// builds the rest on top of them, the `OrderedMap` impl included.
+//
+// The generics of the map go in brackets, as a list of them cannot be matched up to
+// the closing `>`.
+macro_rules! map_api {
+    ([$($generics:tt)*] $map:ty, $iter:ty) => {
+        impl<$($generics)*> $map {
+            /// Sets how inserts treat a key that is already in the map. A new map
+            /// overwrites.
+            pub fn with_duplicates(mut self, dup: $crate::trees::Duplicates<V>) -> Self {
+                self.dup = dup;
+                self
+            }
+
+            /// Inserts `value`, settling an existing `key` by the map's policy. A value
+            /// that gets replaced is handed back in its cell, a merged one in the cell
+            /// it was merged into.
+            pub fn insert(
+                &mut self,
+                key: K,
+                value: V,
+            ) -> Option<::std::rc::Rc<::std::cell::RefCell<V>>> {
+                match self.try_insert(key, value) {
+                    Ok(old) => old,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+
+            /// Like `insert`, but leaves the map untouched and reports
+            /// `TreeError::DuplicateKey` instead of panicking if the policy rejects the key.
+            pub fn try_insert(
+                &mut self,
+                key: K,
+                value: V,
+            ) -> Result<Option<::std::rc::Rc<::std::cell::RefCell<V>>>, $crate::error::TreeError>
+            {
+                if !self.contains_key(&key) {
+                    self.insert_new(key, value);
+                    return Ok(None);
+                }
+                self.tree().unwrap().replace_value(key, value, self.dup).map(Some)
+            }
+
+            pub fn contains_key(&self, key: &K) -> bool {
+                self.get(key).is_some()
+            }
+
+            pub fn len(&self) -> usize {
+                self.tree().map_or(0, |root| root.len())
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.tree().is_none()
+            }
+
+            /// Iterates over `(key, value)` pairs in ascending key order.
+            pub fn iter(&self) -> $iter {
+                <$iter>::new(
+                    self.tree().as_ref(),
+                    ::std::ops::Bound::Unbounded,
+                    ::std::ops::Bound::Unbounded,
+                )
+            }
+
+            /// Iterates over the pairs whose keys fall into `range`, e.g. `map.range(3..7)`.
+            pub fn range<Q: ::std::ops::RangeBounds<K>>(&self, range: Q) -> $iter {
+                <$iter>::new(
+                    self.tree().as_ref(),
+                    range.start_bound().cloned(),
+                    range.end_bound().cloned(),
+                )
+            }
+
+            pub fn first(&self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                self.tree().map(|root| root.first())
+            }
+
+            pub fn last(&self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                self.tree().map(|root| root.last())
+            }
+
+            pub fn pop_first(&mut self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                let (key, _) = self.first()?;
+                self.remove(key).ok().map(|value| (key, value))
+            }
+
+            pub fn pop_last(&mut self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                let (key, _) = self.last()?;
+                self.remove(key).ok().map(|value| (key, value))
+            }
+        }
+
+        impl<$($generics)*> FromIterator<(K, V)> for $map
+        where
+            Self: Default,
+        {
+            /// Collects the pairs; later values win on equal keys.
+            fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+                let mut map = Self::default();
+                for (key, value) in iter {
+                    map.insert(key, value);
+                }
+                map
+            }
+        }
+
+        impl<$($generics)*> $crate::trees::map::OrderedMap<K, V> for $map {
+            type Iter<'a> = $iter
+            where
+                Self: 'a;
+
+            fn insert(
+                &mut self,
+                key: K,
+                value: V,
+            ) -> Option<::std::rc::Rc<::std::cell::RefCell<V>>> {
+                <$map>::insert(self, key, value)
+            }
+
+            fn remove(
+                &mut self,
+                key: K,
+            ) -> Result<::std::rc::Rc<::std::cell::RefCell<V>>, $crate::error::TreeError> {
+                <$map>::remove(self, key)
+            }
+
+            fn get(&self, key: &K) -> Option<::std::rc::Rc<::std::cell::RefCell<V>>> {
+                <$map>::get(self, key)
+            }
+
+            fn len(&self) -> usize {
+                <$map>::len(self)
+            }
+
+            fn iter(&self) -> Self::Iter<'_> {
+                <$map>::iter(self)
+            }
+
+            fn range<Q: ::std::ops::RangeBounds<K>>(&self, range: Q) -> Self::Iter<'_> {
+                <$map>::range(self, range)
+            }
+
+            fn first(&self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                <$map>::first(self)
+            }
+
+            fn last(&self) -> Option<(K, ::std::rc::Rc<::std::cell::RefCell<V>>)> {
+                <$map>::last(self)
+            }
+        }
+    };
+}
+
+pub(super) use map_api;
@@ -0,0 +1,155 @@
+use std::{
+    cell::RefCell,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+};
+
+use super::BinTree;
+
+// Order statistics read the subtree sizes kept in each trunk, so they cost
+// O(height): O(log n) on the AVL tree.
+impl<T, K: Ord + Copy, S, A> BinTree<T, K, S, A> {
+    /// The number of keys. A tree always holds at least its root, so there is no
+    /// `is_empty`; the maps, whose root is optional, have one.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.t.borrow().size
+    }
+
+    /// The number of keys strictly below `key`.
+    pub fn rank(&self, key: K) -> usize {
+        self.count_below(key, false)
+    }
+
+    /// The `i`-th smallest pair, counting from zero.
+    pub fn select(&self, mut i: usize) -> Option<(K, Rc<RefCell<T>>)> {
+        if self.len() <= i {
+            return None;
+        }
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                let left = trunk.left.as_ref().map_or(0, |l| l.t.borrow().size);
+                if i < left {
+                    trunk.left.as_ref().map(|l| Self { t: l.t.clone() })
+                } else if i == left {
+                    return Some((trunk.key, trunk.value.clone()));
+                } else {
+                    i -= left + 1;
+                    trunk.right.as_ref().map(|r| Self { t: r.t.clone() })
+                }
+            };
+            node = next.expect("Subtree sizes are out of sync.");
+        }
+    }
+
+    /// The number of keys falling into `range`.
+    pub fn count_range<R: RangeBounds<K>>(&self, range: R) -> usize {
+        let below_start = match range.start_bound() {
+            Bound::Included(&a) => self.count_below(a, false),
+            Bound::Excluded(&a) => self.count_below(a, true),
+            Bound::Unbounded => 0,
+        };
+        let below_end = match range.end_bound() {
+            Bound::Included(&b) => self.count_below(b, true),
+            Bound::Excluded(&b) => self.count_below(b, false),
+            Bound::Unbounded => self.len(),
+        };
+        below_end.saturating_sub(below_start)
+    }
+
+    pub fn first(&self) -> (K, Rc<RefCell<T>>) {
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                match trunk.left {
+                    Some(ref l) => Self { t: l.t.clone() },
+                    None => return (trunk.key, trunk.value.clone()),
+                }
+            };
+            node = next;
+        }
+    }
+
+    pub fn last(&self) -> (K, Rc<RefCell<T>>) {
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                match trunk.right {
+                    Some(ref r) => Self { t: r.t.clone() },
+                    None => return (trunk.key, trunk.value.clone()),
+                }
+            };
+            node = next;
+        }
+    }
+
+    /// The pair with the largest key less than or equal to `key`.
+    pub fn floor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_below(key, true)
+    }
+
+    /// The pair with the smallest key greater than or equal to `key`.
+    pub fn ceiling(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_above(key, true)
+    }
+
+    /// The pair with the largest key strictly less than `key`.
+    pub fn predecessor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_below(key, false)
+    }
+
+    /// The pair with the smallest key strictly greater than `key`.
+    pub fn successor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_above(key, false)
+    }
+
+    fn nearest_below(&self, key: K, inclusive: bool) -> Option<(K, Rc<RefCell<T>>)> {
+        let mut best = None;
+        let mut node = Some(Self { t: self.t.clone() });
+        while let Some(n) = node {
+            let trunk = n.t.borrow();
+            if trunk.key < key || (inclusive && trunk.key == key) {
+                best = Some((trunk.key, trunk.value.clone()));
+                node = trunk.right.as_ref().map(|r| Self { t: r.t.clone() });
+            } else {
+                node = trunk.left.as_ref().map(|l| Self { t: l.t.clone() });
+            }
+        }
+        best
+    }
+
+    fn nearest_above(&self, key: K, inclusive: bool) -> Option<(K, Rc<RefCell<T>>)> {
+        let mut best = None;
+        let mut node = Some(Self { t: self.t.clone() });
+        while let Some(n) = node {
+            let trunk = n.t.borrow();
+            if key < trunk.key || (inclusive && trunk.key == key) {
+                best = Some((trunk.key, trunk.value.clone()));
+                node = trunk.left.as_ref().map(|l| Self { t: l.t.clone() });
+            } else {
+                node = trunk.right.as_ref().map(|r| Self { t: r.t.clone() });
+            }
+        }
+        best
+    }
+
+    // Counts the keys below `key`, or up to it if `inclusive`.
+    fn count_below(&self, key: K, inclusive: bool) -> usize {
+        let mut count = 0;
+        let mut node = Some(Self { t: self.t.clone() });
+        while let Some(n) = node {
+            let trunk = n.t.borrow();
+            if key < trunk.key || (key == trunk.key && !inclusive) {
+                node = trunk.left.as_ref().map(|l| Self { t: l.t.clone() });
+            } else {
+                count += 1 + trunk.left.as_ref().map_or(0, |l| l.t.borrow().size);
+                node = trunk.right.as_ref().map(|r| Self { t: r.t.clone() });
+            }
+        }
+        count
+    }
+}
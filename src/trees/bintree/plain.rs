@@ -1,7 +1,13 @@
+mod plain_map;
+
 use std::{cell::RefCell, rc::Rc};
 
+use crate::error::TreeError;
+
 use super::{trunk::Trunk, BinTree};
 
+pub use self::plain_map::PlainMap;
+
 pub trait Plain<T, K> {
     fn new(key: K, value: T) -> Self;
 
@@ -10,6 +16,16 @@ pub trait Plain<T, K> {
     fn find(&self, cand: K) -> (bool, K);
 
     fn insert(&self, key: K, value: T);
+
+    fn try_delete(&self, key: K) -> Result<(), TreeError>;
+
+    fn try_insert(&self, key: K, value: T) -> Result<(), TreeError>;
+
+    /// Removes the smallest pair, failing with `TreeError::LastNode` on a single node.
+    fn pop_first(&self) -> Result<(K, Rc<RefCell<T>>), TreeError>;
+
+    /// Removes the largest pair, failing with `TreeError::LastNode` on a single node.
+    fn pop_last(&self) -> Result<(K, Rc<RefCell<T>>), TreeError>;
 }
 
 impl<T, K: Ord + Copy> Trunk<T, K, ()> {
@@ -17,12 +33,16 @@ impl<T, K: Ord + Copy> Trunk<T, K, ()> {
         self.left.is_some() && self.right.is_some()
     }
 
+    // The left branch of the taken leaf takes over its position.
     pub(super) fn take_rightmost_leaf(&mut self) -> BinTree<T, K, ()> {
+        self.size -= 1;
         let right = self.right.as_ref().expect("No right branches at all");
         if right.t.borrow().right.is_some() {
             right.t.borrow_mut().take_rightmost_leaf()
         } else {
-            self.right.take().unwrap()
+            let right = self.right.take().unwrap();
+            self.right = right.t.borrow_mut().left.take();
+            right
         }
     }
 
@@ -31,7 +51,29 @@ impl<T, K: Ord + Copy> Trunk<T, K, ()> {
         if left.t.borrow().right.is_some() {
             left.t.borrow_mut().take_rightmost_leaf()
         } else {
-            self.left.take().unwrap()
+            let left = self.left.take().unwrap();
+            self.left = left.t.borrow_mut().left.take();
+            left
+        }
+    }
+}
+
+impl<T, K: Ord + Copy> BinTree<T, K, ()> {
+    // Decrements the sizes on the path down to, but excluding, the node of `key`.
+    fn shrink_path(&self, key: K) {
+        let mut node = Self { t: self.t.clone() };
+        while node.t.borrow().key != key {
+            let next = {
+                let mut trunk = node.t.borrow_mut();
+                trunk.size -= 1;
+                let next = if key < trunk.key {
+                    trunk.left.as_ref()
+                } else {
+                    trunk.right.as_ref()
+                };
+                next.map(|n| Self { t: n.t.clone() })
+            };
+            node = next.expect("The key is on the path.");
         }
     }
 }
@@ -41,91 +83,117 @@ impl<T, K: Ord + Copy> Plain<T, K> for BinTree<T, K, ()> {
         Self {
             t: Rc::new(RefCell::new(Trunk {
                 key,
-                value,
+                value: Rc::new(RefCell::new(value)),
                 left: None,
                 right: None,
                 state: (),
+                size: 1,
+                aggregate: (),
             })),
         }
     }
 
     fn insert(&self, key: K, value: T) {
-        if key < self.t.borrow().key {
-            if self.t.borrow().left.is_none() {
-                self.t.borrow_mut().left = Some(Self::new(key, value));
-            } else {
-                self.t.borrow().left.as_ref().unwrap().insert(key, value);
-            }
-        } else if self.t.borrow().key < key {
-            if self.t.borrow().right.is_none() {
-                self.t.borrow_mut().right = Some(Self::new(key, value));
-            } else {
-                self.t
-                    .borrow_mut()
-                    .right
-                    .as_ref()
-                    .unwrap()
-                    .insert(key, value);
-            }
-        } else {
-            panic!("The key already exists.")
+        if let Err(e) = self.try_insert(key, value) {
+            panic!("{}", e)
+        }
+    }
+
+    fn try_insert(&self, key: K, value: T) -> Result<(), TreeError> {
+        if self.find_node(key).is_some() {
+            return Err(TreeError::DuplicateKey);
+        }
+        // Walks down in a loop, like `shrink_path`, so a degenerate tree cannot
+        // exhaust the stack.
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let mut trunk = node.t.borrow_mut();
+                trunk.size += 1;
+                let branch = if key < trunk.key {
+                    &mut trunk.left
+                } else {
+                    &mut trunk.right
+                };
+                match branch {
+                    Some(next) => Self { t: next.t.clone() },
+                    None => {
+                        *branch = Some(Self::new(key, value));
+                        return Ok(());
+                    }
+                }
+            };
+            node = next;
         }
     }
 
     fn delete(&self, key: K) {
-        let append = |this: Option<Self>, to: &Self| {
-            let this_key = this.as_ref().unwrap().t.borrow().key;
-            if to.t.borrow().key == this_key {
-                // In this case branch is self. Do nothing.
-            } else if to.t.borrow().key < this_key {
-                to.t.borrow_mut().right = this;
-            } else {
-                to.t.borrow_mut().left = this;
-            }
-        };
+        if let Err(e) = self.try_delete(key) {
+            panic!("{}", e)
+        }
+    }
 
-        let (parent, target, pos) = self.find_parent(key);
-        if target.t.borrow().is_bifurcating() {
-            let replacing_reaf = target.t.borrow_mut().take_floor_leaf();
-            append(target.t.borrow_mut().right.take(), &replacing_reaf);
-            append(target.t.borrow_mut().left.take(), &replacing_reaf);
+    fn try_delete(&self, key: K) -> Result<(), TreeError> {
+        let (parent, target, pos) = self.find_parent(key)?;
+        let replacing = if target.t.borrow().is_bifurcating() {
+            let replacing = target.t.borrow_mut().take_floor_leaf();
+            let mut trunk = replacing.t.borrow_mut();
+            trunk.left = target.t.borrow_mut().left.take();
+            trunk.right = target.t.borrow_mut().right.take();
+            trunk.update_size();
+            drop(trunk);
+            Some(replacing)
+        } else {
+            let mut trunk = target.t.borrow_mut();
+            trunk.left.take().or_else(|| trunk.right.take())
+        };
 
-            if let Some(parent) = parent {
-                append(Some(replacing_reaf), &parent);
+        if let Some(parent) = parent {
+            self.shrink_path(key);
+            if pos == -1 {
+                parent.t.borrow_mut().left = replacing;
+            } else {
+                parent.t.borrow_mut().right = replacing;
             }
         } else {
-            if let Some(parent) = parent {
-                if target.t.borrow().right.is_some() {
-                    append(target.t.borrow_mut().right.take(), &parent);
-                } else if target.t.borrow().left.is_some() {
-                    append(target.t.borrow_mut().left.take(), &parent);
-                } else {
-                    // Just pull it out, no appending
-                    if pos == -1 {
-                        parent.t.borrow_mut().left.take();
-                    } else {
-                        parent.t.borrow_mut().right.take();
-                    }
-                }
-            }
+            // The root node is kept in place so the caller's handle stays valid.
+            let replacing = replacing.ok_or(TreeError::LastNode)?;
+            let mut from = replacing.t.borrow_mut();
+            let mut root = self.t.borrow_mut();
+            root.key = from.key;
+            root.value = from.value.clone();
+            root.left = from.left.take();
+            root.right = from.right.take();
+            root.size = from.size;
         }
+        Ok(())
+    }
+
+    fn pop_first(&self) -> Result<(K, Rc<RefCell<T>>), TreeError> {
+        let first = self.first();
+        self.try_delete(first.0)?;
+        Ok(first)
+    }
+
+    fn pop_last(&self) -> Result<(K, Rc<RefCell<T>>), TreeError> {
+        let last = self.last();
+        self.try_delete(last.0)?;
+        Ok(last)
     }
 
     fn find(&self, cand: K) -> (bool, K) {
         if self.t.borrow().key == cand {
-            return (true, cand);
+            (true, cand)
         } else if cand < self.t.borrow().key {
             if self.t.borrow().left.is_none() {
-                return (false, self.t.borrow().key);
+                (false, self.t.borrow().key)
             } else {
-                return self.t.borrow().left.as_ref().unwrap().find(cand);
+                self.t.borrow().left.as_ref().unwrap().find(cand)
             }
+        } else if self.t.borrow().right.is_none() {
+            (false, self.t.borrow().key)
         } else {
-            if self.t.borrow().right.is_none() {
-                return (false, self.t.borrow().key);
-            } else {
-                return self.t.borrow().right.as_ref().unwrap().find(cand);
-            }
+            self.t.borrow().right.as_ref().unwrap().find(cand)
         }
     }
 }
@@ -0,0 +1,94 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{iter::Iter, map::map_api, BinTree},
+        Duplicates,
+    },
+};
+
+use super::Plain;
+
+/// A plain binary search tree behind a handle that owns its root.
+///
+/// A bare `BinTree` always holds its root node; the map drops the root along with the
+/// last pair instead, so the tree may start and end empty.
+pub struct PlainMap<K: Ord + Copy, V> {
+    root: Option<BinTree<V, K, ()>>,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> PlainMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Takes over an existing tree.
+    pub fn from_root(root: BinTree<V, K, ()>) -> Self {
+        Self {
+            root: Some(root),
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, ()>> {
+        self.root
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, ()>> {
+        self.root.as_ref().map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // Inserts a key the map does not hold yet.
+    fn insert_new(&mut self, key: K, value: V) {
+        match self.root.as_ref() {
+            Some(root) => root.insert(key, value),
+            None => self.root = Some(BinTree::new(key, value)),
+        }
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        let value = self.get(&key).ok_or(TreeError::KeyNotFound)?;
+        if self.len() == 1 {
+            self.root = None;
+        } else {
+            self.root.as_ref().unwrap().try_delete(key)?;
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        let node = self.root.as_ref()?.find_node(*key)?;
+        let value = node.t.borrow().value.clone();
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+}
+
+impl<K: Ord + Copy + Debug, V> PlainMap<K, V> {
+    /// Verifies the tree; an empty map is always valid.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        match self.root.as_ref() {
+            Some(root) => root.check_invariants(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K: Ord + Copy, V> Default for PlainMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+map_api!([K: Ord + Copy, V] PlainMap<K, V>, Iter<V, K, ()>);
@@ -0,0 +1,501 @@
+mod rb_map;
+
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::error::TreeError;
+
+use super::{avl::Pair, trunk::Trunk, BinTree};
+
+pub use self::rb_map::RbMap;
+pub use super::entry::{Entry, OccupiedEntry, VacantEntry};
+
+/// A red-black tree: no red node has a red child and every path from a node down to
+/// its leaves passes the same number of black nodes.
+///
+/// Like the AVL tree, the operations consume the root and hand back the new one, as
+/// rotations may move it. Rebalancing needs at most two rotations per insert and three
+/// per delete.
+pub trait RedBlack<T, K: Ord + Copy> {
+    fn new(key: K, value: T) -> Self;
+
+    /// Returns whether the key was found, and the remaining tree, if any.
+    fn delete(self, key: K) -> (bool, Option<Self>)
+    where
+        Self: Sized;
+
+    fn find(&self, cand: K) -> (bool, K);
+
+    fn insert(self, key: K, value: T) -> Self;
+
+    /// Like `delete`, but reports a missing key as `TreeError::KeyNotFound`.
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Option<Self>)
+    where
+        Self: Sized;
+
+    /// Like `insert`, but leaves the tree untouched and reports
+    /// `TreeError::DuplicateKey` instead of panicking.
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Self);
+
+    /// Returns the cell holding the value for `key`, shared with the tree.
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>>;
+
+    /// Edits the value for `key` in place and returns what `edit` returns.
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R>;
+
+    fn contains_key(&self, key: &K) -> bool;
+
+    fn entry(self, key: K) -> Entry<T, K, (), Color>
+    where
+        Self: Sized;
+
+    /// Removes the smallest pair, handing back the remaining tree, if any.
+    fn pop_first(self) -> (Pair<T, K>, Option<Self>)
+    where
+        Self: Sized;
+
+    /// Removes the largest pair, handing back the remaining tree, if any.
+    fn pop_last(self) -> (Pair<T, K>, Option<Self>)
+    where
+        Self: Sized;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Black,
+}
+
+// Missing children count as black.
+fn is_red<T, K: Ord + Copy>(node: &Option<BinTree<T, K, Color>>) -> bool {
+    node.as_ref()
+        .is_some_and(|n| n.t.borrow().state == Color::Red)
+}
+
+impl<T, K: Ord + Copy> BinTree<T, K, Color> {
+    fn color(&self) -> Color {
+        self.t.borrow().state
+    }
+
+    fn paint(&self, color: Color) {
+        self.t.borrow_mut().state = color;
+    }
+
+    fn rotate_left(self) -> Self {
+        let right = self.t.borrow_mut().right.take().expect("No right branch.");
+        self.t.borrow_mut().right = right.t.borrow_mut().left.take();
+        self.t.borrow_mut().update_size();
+        right.t.borrow_mut().left = Some(self);
+        right.t.borrow_mut().update_size();
+        right
+    }
+
+    fn rotate_right(self) -> Self {
+        let left = self.t.borrow_mut().left.take().expect("No left branch.");
+        self.t.borrow_mut().left = left.t.borrow_mut().right.take();
+        self.t.borrow_mut().update_size();
+        left.t.borrow_mut().right = Some(self);
+        left.t.borrow_mut().update_size();
+        left
+    }
+
+    // Inserts `key` below self. On a duplicate the subtree comes back as it was.
+    fn insert_below(self, key: K, value: T) -> (Result<(), TreeError>, Self) {
+        let result = if key < self.t.borrow().key {
+            let left = self.t.borrow_mut().left.take();
+            let (result, left) = match left {
+                Some(left) => left.insert_below(key, value),
+                None => (Ok(()), Self::leaf(key, value)),
+            };
+            self.t.borrow_mut().left = Some(left);
+            result
+        } else if self.t.borrow().key < key {
+            let right = self.t.borrow_mut().right.take();
+            let (result, right) = match right {
+                Some(right) => right.insert_below(key, value),
+                None => (Ok(()), Self::leaf(key, value)),
+            };
+            self.t.borrow_mut().right = Some(right);
+            result
+        } else {
+            Err(TreeError::DuplicateKey)
+        };
+        if result.is_err() {
+            return (result, self);
+        }
+        self.t.borrow_mut().update_size();
+        (result, self.fix_red_pair())
+    }
+
+    fn leaf(key: K, value: T) -> Self {
+        let leaf = <Self as RedBlack<T, K>>::new(key, value);
+        leaf.paint(Color::Red);
+        leaf
+    }
+
+    // Seen from the grandparent, resolves a red child with a red child of its own.
+    fn fix_red_pair(self) -> Self {
+        let (left_pair, right_pair) = {
+            let trunk = self.t.borrow();
+            let pair = |child: &Option<Self>| {
+                is_red(child)
+                    && child.as_ref().is_some_and(|c| {
+                        let c = c.t.borrow();
+                        is_red(&c.left) || is_red(&c.right)
+                    })
+            };
+            (pair(&trunk.left), pair(&trunk.right))
+        };
+        if !left_pair && !right_pair {
+            return self;
+        }
+        let (left_red, right_red) = {
+            let trunk = self.t.borrow();
+            (is_red(&trunk.left), is_red(&trunk.right))
+        };
+        if left_red && right_red {
+            // A red uncle: push the red up and let the levels above deal with it.
+            let trunk = self.t.borrow();
+            trunk.left.as_ref().unwrap().paint(Color::Black);
+            trunk.right.as_ref().unwrap().paint(Color::Black);
+            drop(trunk);
+            self.paint(Color::Red);
+            return self;
+        }
+        let top = if left_pair {
+            let left = self.t.borrow_mut().left.take().unwrap();
+            let left = if is_red(&left.t.borrow().right) {
+                left.rotate_left()
+            } else {
+                left
+            };
+            self.t.borrow_mut().left = Some(left);
+            self.rotate_right()
+        } else {
+            let right = self.t.borrow_mut().right.take().unwrap();
+            let right = if is_red(&right.t.borrow().left) {
+                right.rotate_right()
+            } else {
+                right
+            };
+            self.t.borrow_mut().right = Some(right);
+            self.rotate_left()
+        };
+        top.paint(Color::Black);
+        {
+            let trunk = top.t.borrow();
+            trunk.left.as_ref().unwrap().paint(Color::Red);
+            trunk.right.as_ref().unwrap().paint(Color::Red);
+        }
+        top
+    }
+
+    // Removes `key` below self. Returns whether it was found, the remaining subtree
+    // and whether its black height dropped by one.
+    fn delete_below(self, key: K) -> (bool, Option<Self>, bool) {
+        let here = self.t.borrow().key;
+        if key < here {
+            let left = self.t.borrow_mut().left.take();
+            let Some(left) = left else {
+                return (false, Some(self), false);
+            };
+            let (found, left, shorter) = left.delete_below(key);
+            self.t.borrow_mut().left = left;
+            self.t.borrow_mut().update_size();
+            let (top, shorter) = if shorter {
+                self.fix_left_shorter()
+            } else {
+                (self, false)
+            };
+            (found, Some(top), shorter)
+        } else if here < key {
+            let right = self.t.borrow_mut().right.take();
+            let Some(right) = right else {
+                return (false, Some(self), false);
+            };
+            let (found, right, shorter) = right.delete_below(key);
+            self.t.borrow_mut().right = right;
+            self.t.borrow_mut().update_size();
+            let (top, shorter) = if shorter {
+                self.fix_right_shorter()
+            } else {
+                (self, false)
+            };
+            (found, Some(top), shorter)
+        } else {
+            let (left, right) = {
+                let mut trunk = self.t.borrow_mut();
+                (trunk.left.take(), trunk.right.take())
+            };
+            match (left, right) {
+                (Some(left), Some(right)) => {
+                    // The smallest node on the right takes the place and color of self.
+                    let (min, right, shorter) = right.take_min();
+                    {
+                        let mut trunk = min.t.borrow_mut();
+                        trunk.left = Some(left);
+                        trunk.right = right;
+                        trunk.state = self.color();
+                        trunk.update_size();
+                    }
+                    let (top, shorter) = if shorter {
+                        min.fix_right_shorter()
+                    } else {
+                        (min, false)
+                    };
+                    (true, Some(top), shorter)
+                }
+                (child, None) | (None, child) => {
+                    let (child, shorter) = self.lift(child);
+                    (true, child, shorter)
+                }
+            }
+        }
+    }
+
+    // Puts the only child of the removed node self in its place.
+    fn lift(&self, child: Option<Self>) -> (Option<Self>, bool) {
+        if self.color() == Color::Red {
+            (child, false)
+        } else if is_red(&child) {
+            child.as_ref().unwrap().paint(Color::Black);
+            (child, false)
+        } else {
+            (child, true)
+        }
+    }
+
+    // Detaches the node with the smallest key, returning it along with the rest of the
+    // subtree and whether its black height dropped.
+    fn take_min(self) -> (Self, Option<Self>, bool) {
+        let left = self.t.borrow_mut().left.take();
+        match left {
+            None => {
+                let right = self.t.borrow_mut().right.take();
+                let (rest, shorter) = self.lift(right);
+                self.t.borrow_mut().update_size();
+                (self, rest, shorter)
+            }
+            Some(left) => {
+                let (min, left, shorter) = left.take_min();
+                self.t.borrow_mut().left = left;
+                self.t.borrow_mut().update_size();
+                let (top, shorter) = if shorter {
+                    self.fix_left_shorter()
+                } else {
+                    (self, false)
+                };
+                (min, Some(top), shorter)
+            }
+        }
+    }
+
+    // The left branch has lost a black level against the right one.
+    fn fix_left_shorter(self) -> (Self, bool) {
+        let sibling_red = is_red(&self.t.borrow().right);
+        if sibling_red {
+            // Turn the red sibling into the parent; the new sibling is black.
+            self.paint(Color::Red);
+            let top = self.rotate_left();
+            top.paint(Color::Black);
+            let left = top.t.borrow_mut().left.take().unwrap();
+            let (left, _) = left.fix_left_shorter();
+            top.t.borrow_mut().left = Some(left);
+            top.t.borrow_mut().update_size();
+            return (top, false);
+        }
+        let (near_red, far_red) = {
+            let trunk = self.t.borrow();
+            let sibling = trunk.right.as_ref().unwrap().t.borrow();
+            (is_red(&sibling.left), is_red(&sibling.right))
+        };
+        if !near_red && !far_red {
+            self.t.borrow().right.as_ref().unwrap().paint(Color::Red);
+            return if self.color() == Color::Red {
+                self.paint(Color::Black);
+                (self, false)
+            } else {
+                (self, true)
+            };
+        }
+        if !far_red {
+            let sibling = self.t.borrow_mut().right.take().unwrap();
+            self.t.borrow_mut().right = Some(sibling.rotate_right());
+        }
+        let color = self.color();
+        self.paint(Color::Black);
+        let top = self.rotate_left();
+        top.paint(color);
+        top.t.borrow().right.as_ref().unwrap().paint(Color::Black);
+        (top, false)
+    }
+
+    // The right branch has lost a black level against the left one.
+    fn fix_right_shorter(self) -> (Self, bool) {
+        let sibling_red = is_red(&self.t.borrow().left);
+        if sibling_red {
+            self.paint(Color::Red);
+            let top = self.rotate_right();
+            top.paint(Color::Black);
+            let right = top.t.borrow_mut().right.take().unwrap();
+            let (right, _) = right.fix_right_shorter();
+            top.t.borrow_mut().right = Some(right);
+            top.t.borrow_mut().update_size();
+            return (top, false);
+        }
+        let (near_red, far_red) = {
+            let trunk = self.t.borrow();
+            let sibling = trunk.left.as_ref().unwrap().t.borrow();
+            (is_red(&sibling.right), is_red(&sibling.left))
+        };
+        if !near_red && !far_red {
+            self.t.borrow().left.as_ref().unwrap().paint(Color::Red);
+            return if self.color() == Color::Red {
+                self.paint(Color::Black);
+                (self, false)
+            } else {
+                (self, true)
+            };
+        }
+        if !far_red {
+            let sibling = self.t.borrow_mut().left.take().unwrap();
+            self.t.borrow_mut().left = Some(sibling.rotate_left());
+        }
+        let color = self.color();
+        self.paint(Color::Black);
+        let top = self.rotate_right();
+        top.paint(color);
+        top.t.borrow().left.as_ref().unwrap().paint(Color::Black);
+        (top, false)
+    }
+
+    // The number of black nodes on every path down from self, leaves excluded.
+    fn black_height(&self) -> Result<usize, TreeError> {
+        let trunk = self.t.borrow();
+        let red = trunk.state == Color::Red;
+        if red && (is_red(&trunk.left) || is_red(&trunk.right)) {
+            return Err(TreeError::CorruptStructure(
+                "a red node has a red child".to_string(),
+            ));
+        }
+        let left = trunk.left.as_ref().map_or(Ok(0), |l| l.black_height())?;
+        let right = trunk.right.as_ref().map_or(Ok(0), |r| r.black_height())?;
+        if left != right {
+            return Err(TreeError::CorruptStructure(format!(
+                "black heights {} and {} below a node",
+                left, right
+            )));
+        }
+        Ok(left + if red { 0 } else { 1 })
+    }
+}
+
+impl<T, K: Ord + Copy + Debug> BinTree<T, K, Color> {
+    /// Verifies the key order, the subtree sizes, that the root is black, that no red
+    /// node has a red child and that black heights agree, reporting the first violation.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        self.check_with(|_, _, _, _| Ok(()))?;
+        if self.color() == Color::Red {
+            return Err(TreeError::CorruptStructure(format!(
+                "root {:?} is red",
+                self.t.borrow().key
+            )));
+        }
+        self.black_height().map(|_| ())
+    }
+}
+
+impl<T, K: Ord + Copy> RedBlack<T, K> for BinTree<T, K, Color> {
+    fn new(key: K, value: T) -> Self {
+        Self {
+            t: Rc::new(RefCell::new(Trunk {
+                key,
+                value: Rc::new(RefCell::new(value)),
+                left: None,
+                right: None,
+                state: Color::Black,
+                size: 1,
+                aggregate: (),
+            })),
+        }
+    }
+
+    fn insert(self, key: K, value: T) -> Self {
+        match self.try_insert(key, value) {
+            (Ok(()), root) => root,
+            (Err(e), _) => panic!("{}", e),
+        }
+    }
+
+    fn try_insert(self, key: K, value: T) -> (Result<(), TreeError>, Self) {
+        let (result, root) = self.insert_below(key, value);
+        root.paint(Color::Black);
+        (result, root)
+    }
+
+    fn delete(self, key: K) -> (bool, Option<Self>) {
+        let (found, root, _) = self.delete_below(key);
+        if let Some(root) = root.as_ref() {
+            root.paint(Color::Black);
+        }
+        (found, root)
+    }
+
+    fn try_delete(self, key: K) -> (Result<(), TreeError>, Option<Self>) {
+        match self.delete(key) {
+            (true, root) => (Ok(()), root),
+            (false, root) => (Err(TreeError::KeyNotFound), root),
+        }
+    }
+
+    fn find(&self, cand: K) -> (bool, K) {
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                let next = if cand < trunk.key {
+                    trunk.left.as_ref()
+                } else if trunk.key < cand {
+                    trunk.right.as_ref()
+                } else {
+                    return (true, cand);
+                };
+                match next {
+                    Some(n) => Self { t: n.t.clone() },
+                    None => return (false, trunk.key),
+                }
+            };
+            node = next;
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>> {
+        self.find_node(*key)
+            .map(|node| node.t.borrow().value.clone())
+    }
+
+    fn get_mut<R>(&self, key: &K, edit: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let cell = RedBlack::get(self, key)?;
+        let result = edit(&mut cell.borrow_mut());
+        Some(result)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.find_node(*key).is_some()
+    }
+
+    fn entry(self, key: K) -> Entry<T, K, (), Color> {
+        Entry::new(self, key, RedBlack::insert)
+    }
+
+    fn pop_first(self) -> (Pair<T, K>, Option<Self>) {
+        let first = self.first();
+        let (_, root) = self.delete(first.0);
+        (first, root)
+    }
+
+    fn pop_last(self) -> (Pair<T, K>, Option<Self>) {
+        let last = self.last();
+        let (_, root) = self.delete(last.0);
+        (last, root)
+    }
+}
@@ -0,0 +1,89 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{iter::Iter, map::map_api, BinTree},
+        Duplicates,
+    },
+};
+
+use super::{Color, RedBlack};
+
+/// A red-black tree behind a handle that owns its root.
+///
+/// The consuming operations of `RedBlack` hand back a new root; the map stores it in
+/// place, so callers work through `&mut self` and the tree may be empty.
+pub struct RbMap<K: Ord + Copy, V> {
+    root: Option<BinTree<V, K, Color>>,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> RbMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Takes over an existing tree.
+    pub fn from_root(root: BinTree<V, K, Color>) -> Self {
+        Self {
+            root: Some(root),
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, Color>> {
+        self.root
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, Color>> {
+        self.root.as_ref().map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // Inserts a key the map does not hold yet.
+    fn insert_new(&mut self, key: K, value: V) {
+        self.root = Some(match self.root.take() {
+            Some(root) => root.insert(key, value),
+            None => BinTree::new(key, value),
+        });
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        let value = self.get(&key).ok_or(TreeError::KeyNotFound)?;
+        let (_, root) = self.root.take().unwrap().delete(key);
+        self.root = root;
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        self.root.as_ref().and_then(|root| RedBlack::get(root, key))
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+}
+
+impl<K: Ord + Copy + Debug, V> RbMap<K, V> {
+    /// Verifies the tree; an empty map is always valid.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        match self.root.as_ref() {
+            Some(root) => root.check_invariants(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K: Ord + Copy, V> Default for RbMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+map_api!([K: Ord + Copy, V] RbMap<K, V>, Iter<V, K, Color>);
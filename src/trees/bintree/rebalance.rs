@@ -0,0 +1,136 @@
+use std::{cell::RefCell, cmp, mem, rc::Rc};
+
+use super::{avl::State, trunk::Trunk, BinTree};
+
+impl<T, K: Ord + Copy> BinTree<T, K, ()> {
+    // Rotates the left child up. The pairs of the two nodes are swapped rather than the
+    // nodes, so that self stays on top and nothing above it needs relinking.
+    fn rotate_right_in_place(&self) {
+        let mut top = self.t.borrow_mut();
+        let child = top
+            .left
+            .take()
+            .expect("A right rotation needs a left child.");
+        {
+            let mut below = child.t.borrow_mut();
+            mem::swap(&mut top.key, &mut below.key);
+            mem::swap(&mut top.value, &mut below.value);
+            top.left = below.left.take();
+            below.left = below.right.take();
+            below.right = top.right.take();
+            below.update_size();
+        }
+        top.right = Some(child);
+        top.update_size();
+    }
+
+    fn rotate_left_in_place(&self) {
+        let mut top = self.t.borrow_mut();
+        let child = top
+            .right
+            .take()
+            .expect("A left rotation needs a right child.");
+        {
+            let mut below = child.t.borrow_mut();
+            mem::swap(&mut top.key, &mut below.key);
+            mem::swap(&mut top.value, &mut below.value);
+            top.right = below.right.take();
+            below.right = below.left.take();
+            below.left = top.left.take();
+            below.update_size();
+        }
+        top.left = Some(child);
+        top.update_size();
+    }
+
+    // Rotates every left child up until the tree is a path descending to the right.
+    fn straighten(&self) {
+        let mut node = Some(Self { t: self.t.clone() });
+        while let Some(n) = node {
+            while n.t.borrow().left.is_some() {
+                n.rotate_right_in_place();
+            }
+            node = n.t.borrow().right.as_ref().map(|r| Self { t: r.t.clone() });
+        }
+    }
+
+    // Rotates every other node of the right spine up, count times.
+    fn compress(&self, count: usize) {
+        let mut node = Self { t: self.t.clone() };
+        for _ in 0..count {
+            node.rotate_left_in_place();
+            let next = node
+                .t
+                .borrow()
+                .right
+                .as_ref()
+                .map(|r| Self { t: r.t.clone() });
+            match next {
+                Some(next) => node = next,
+                None => break,
+            }
+        }
+    }
+
+    /// Rebalances the tree with the Day–Stout–Warren algorithm in O(n) time and O(1)
+    /// extra space. All levels but the lowest end up full.
+    ///
+    /// The pairs move between the nodes, so `self` remains the root.
+    pub fn rebalance(&self) {
+        self.straighten();
+        let n = self.len();
+        // The nodes of the largest full tree that fits; the rest go to the lowest level.
+        let mut full = (1 << (usize::BITS - (n + 1).leading_zeros() - 1)) - 1;
+        self.compress(n - full);
+        while 1 < full {
+            full /= 2;
+            self.compress(full);
+        }
+    }
+
+    /// Rebalances the tree and turns it into an AVL tree holding the same values.
+    pub fn convert_to_avl(self) -> BinTree<T, K, State> {
+        self.rebalance();
+        Self::to_avl(self).0
+    }
+
+    // Copies the rebalanced nodes, whose depth is logarithmic, deriving their states
+    // from the heights of their branches.
+    fn to_avl(node: Self) -> (BinTree<T, K, State>, usize) {
+        let (key, value, left, right, size) = {
+            let mut trunk = node.t.borrow_mut();
+            (
+                trunk.key,
+                trunk.value.clone(),
+                trunk.left.take(),
+                trunk.right.take(),
+                trunk.size,
+            )
+        };
+        let (left, hl) = left.map_or((None, 0), |l| {
+            let (l, h) = Self::to_avl(l);
+            (Some(l), h)
+        });
+        let (right, hr) = right.map_or((None, 0), |r| {
+            let (r, h) = Self::to_avl(r);
+            (Some(r), h)
+        });
+        let state = match hl.cmp(&hr) {
+            cmp::Ordering::Greater => State::LeftSided,
+            cmp::Ordering::Equal => State::Balanced,
+            cmp::Ordering::Less => State::RightSided,
+        };
+        let tree = BinTree {
+            t: Rc::new(RefCell::new(Trunk {
+                key,
+                value,
+                left,
+                right,
+                state,
+                size,
+                aggregate: (),
+            })),
+        };
+        (tree, cmp::max(hl, hr) + 1)
+    }
+}
@@ -0,0 +1,68 @@
+mod scapegoat_map;
+
+use super::BinTree;
+
+pub use self::scapegoat_map::ScapegoatMap;
+
+impl<T, K: Ord + Copy> BinTree<T, K, ()> {
+    /// Relinks the nodes into a perfectly balanced tree in O(n) and returns its root.
+    /// The nodes themselves are kept, so handles to values stay valid.
+    pub fn rebuild(self) -> Self {
+        let nodes = self.flatten();
+        Self::link(&nodes).expect("A tree holds at least its root.")
+    }
+
+    // Detaches all nodes and lists them in key order.
+    fn flatten(self) -> Vec<Self> {
+        let mut nodes = Vec::with_capacity(self.len());
+        let mut stack = Vec::new();
+        let mut next = Some(self);
+        loop {
+            while let Some(node) = next {
+                next = node.t.borrow_mut().left.take();
+                stack.push(node);
+            }
+            match stack.pop() {
+                Some(node) => {
+                    next = node.t.borrow_mut().right.take();
+                    nodes.push(node);
+                }
+                None => return nodes,
+            }
+        }
+    }
+
+    fn link(nodes: &[Self]) -> Option<Self> {
+        if nodes.is_empty() {
+            return None;
+        }
+        let mid = nodes.len() / 2;
+        let node = Self {
+            t: nodes[mid].t.clone(),
+        };
+        {
+            let mut trunk = node.t.borrow_mut();
+            trunk.left = Self::link(&nodes[..mid]);
+            trunk.right = Self::link(&nodes[mid + 1..]);
+            trunk.update_size();
+        }
+        Some(node)
+    }
+
+    // The depth of the deepest node, counting the root as zero.
+    fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(Self { t: self.t.clone() }, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            let trunk = node.t.borrow();
+            for child in [trunk.left.as_ref(), trunk.right.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                stack.push((Self { t: child.t.clone() }, depth + 1));
+            }
+        }
+        deepest
+    }
+}
@@ -0,0 +1,199 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{iter::Iter, map::map_api, plain::Plain, BinTree},
+        Duplicates,
+    },
+};
+
+/// A scapegoat tree behind a handle that owns its root.
+///
+/// The nodes are those of a plain tree. Instead of balance flags the map watches the
+/// depth of inserts against the size of the tree: once a path grows too long, the
+/// lowest ancestor with a branch heavier than `alpha` times its own size is rebuilt.
+/// After enough deletes the whole tree is rebuilt.
+pub struct ScapegoatMap<K: Ord + Copy, V> {
+    root: Option<BinTree<V, K, ()>>,
+    alpha: f64,
+    // The largest size since the last rebuild of the whole tree.
+    max_len: usize,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> ScapegoatMap<K, V> {
+    /// An empty map with `alpha = 2/3`.
+    pub fn new() -> Self {
+        Self::with_alpha(2.0 / 3.0)
+    }
+
+    /// An empty map with the given weight bound. A smaller `alpha` keeps the tree
+    /// flatter at the price of more rebuilds.
+    pub fn with_alpha(alpha: f64) -> Self {
+        assert!(0.5 < alpha && alpha < 1.0, "alpha must be in (0.5, 1).");
+        Self {
+            root: None,
+            alpha,
+            max_len: 0,
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Takes over a plain tree of any shape with `alpha = 2/3`, rebuilding it balanced.
+    pub fn from_root(root: BinTree<V, K, ()>) -> Self {
+        Self::from_root_with_alpha(root, 2.0 / 3.0)
+    }
+
+    /// Takes over a plain tree of any shape with the given weight bound, rebuilding it
+    /// balanced.
+    pub fn from_root_with_alpha(root: BinTree<V, K, ()>, alpha: f64) -> Self {
+        let mut map = Self::with_alpha(alpha);
+        map.max_len = root.len();
+        map.root = Some(root.rebuild());
+        map
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, ()>> {
+        self.root
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, ()>> {
+        self.root.as_ref().map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // The depth no node may exceed in a tree of len nodes: log base 1 / alpha of len.
+    fn depth_bound(&self, len: usize) -> usize {
+        ((len as f64).ln() / (1.0 / self.alpha).ln()).floor() as usize
+    }
+
+    // Inserts a key the map does not hold yet.
+    fn insert_new(&mut self, key: K, value: V) {
+        let Some(root) = self.root.as_ref() else {
+            self.root = Some(BinTree::new(key, value));
+            self.max_len = 1;
+            return;
+        };
+        // Walk down to the leaf the new node hangs from, remembering the path.
+        let mut path = Vec::new();
+        let mut node = BinTree { t: root.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                if key < trunk.key {
+                    trunk.left.as_ref().map(|l| BinTree { t: l.t.clone() })
+                } else {
+                    trunk.right.as_ref().map(|r| BinTree { t: r.t.clone() })
+                }
+            };
+            path.push(node);
+            match next {
+                Some(next) => node = next,
+                None => break,
+            }
+        }
+
+        let leaf = path.last().unwrap();
+        let below = key < leaf.t.borrow().key;
+        if below {
+            leaf.t.borrow_mut().left = Some(BinTree::new(key, value));
+        } else {
+            leaf.t.borrow_mut().right = Some(BinTree::new(key, value));
+        }
+        for node in path.iter() {
+            node.t.borrow_mut().size += 1;
+        }
+        let len = self.len();
+        self.max_len = self.max_len.max(len);
+
+        if self.depth_bound(len) < path.len() {
+            self.rebuild_scapegoat(path);
+        }
+    }
+
+    // Rebuilds the lowest node on the path with a branch outweighing alpha times itself.
+    fn rebuild_scapegoat(&mut self, path: Vec<BinTree<V, K, ()>>) {
+        let heavy = |node: &BinTree<V, K, ()>| {
+            let trunk = node.t.borrow();
+            let left = trunk.left.as_ref().map_or(0, |l| l.len());
+            let right = trunk.right.as_ref().map_or(0, |r| r.len());
+            self.alpha * (trunk.size as f64) < left.max(right) as f64
+        };
+        let Some(pos) = path.iter().rposition(heavy) else {
+            return;
+        };
+        let scapegoat = BinTree {
+            t: path[pos].t.clone(),
+        };
+        let is_left = pos > 0
+            && path[pos - 1]
+                .t
+                .borrow()
+                .left
+                .as_ref()
+                .is_some_and(|l| Rc::ptr_eq(&l.t, &scapegoat.t));
+        let rebuilt = scapegoat.rebuild();
+        match pos {
+            0 => self.root = Some(rebuilt),
+            _ if is_left => path[pos - 1].t.borrow_mut().left = Some(rebuilt),
+            _ => path[pos - 1].t.borrow_mut().right = Some(rebuilt),
+        }
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        let value = self.get(&key).ok_or(TreeError::KeyNotFound)?;
+        if self.len() == 1 {
+            self.root = None;
+            self.max_len = 0;
+            return Ok(value);
+        }
+        self.root.as_ref().unwrap().try_delete(key)?;
+        let len = self.len();
+        if (len as f64) < self.alpha * (self.max_len as f64) {
+            self.root = self.root.take().map(|root| root.rebuild());
+            self.max_len = len;
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        let node = self.root.as_ref()?.find_node(*key)?;
+        let value = node.t.borrow().value.clone();
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.max_len = 0;
+    }
+}
+
+impl<K: Ord + Copy + Debug, V> ScapegoatMap<K, V> {
+    /// Verifies the key order, the subtree sizes and that no node lies deeper than
+    /// the bound the rebuilds maintain, reporting the first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        let Some(root) = self.root.as_ref() else {
+            return Ok(());
+        };
+        root.check_invariants()?;
+        let (depth, bound) = (root.depth(), self.depth_bound(self.max_len) + 1);
+        if bound < depth {
+            return Err(TreeError::CorruptStructure(format!(
+                "depth {} exceeds the bound {} for {} nodes",
+                depth, bound, self.max_len
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Ord + Copy, V> Default for ScapegoatMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+map_api!([K: Ord + Copy, V] ScapegoatMap<K, V>, Iter<V, K, ()>);
@@ -0,0 +1,86 @@
+mod splay_map;
+
+use super::BinTree;
+
+pub use self::splay_map::SplayMap;
+
+impl<T, K: Ord + Copy> BinTree<T, K, ()> {
+    /// Brings the node holding `key`, or the last node on the search path for it, to
+    /// the root and returns the new root.
+    ///
+    /// Splays top-down: the nodes passed on the way are hung onto a left and a right
+    /// tree which are reassembled below the new root in the end, so nothing recurses.
+    pub fn splay(self, key: K) -> Self {
+        // The nodes hung on the right spine of the left tree, and the left spine of the
+        // right tree, in the order they were passed.
+        let mut lower: Vec<Self> = Vec::new();
+        let mut upper: Vec<Self> = Vec::new();
+        let mut node = self;
+        loop {
+            let here = node.t.borrow().key;
+            if key < here {
+                let Some(left) = node.t.borrow_mut().left.take() else {
+                    break;
+                };
+                if key < left.t.borrow().key {
+                    // Zig-zig: rotate right before going on.
+                    node.t.borrow_mut().left = left.t.borrow_mut().right.take();
+                    node.t.borrow_mut().update_size();
+                    left.t.borrow_mut().right = Some(node);
+                    node = left;
+                    let Some(next) = node.t.borrow_mut().left.take() else {
+                        break;
+                    };
+                    upper.push(node);
+                    node = next;
+                } else {
+                    upper.push(node);
+                    node = left;
+                }
+            } else if here < key {
+                let Some(right) = node.t.borrow_mut().right.take() else {
+                    break;
+                };
+                if right.t.borrow().key < key {
+                    // Zag-zag: rotate left before going on.
+                    node.t.borrow_mut().right = right.t.borrow_mut().left.take();
+                    node.t.borrow_mut().update_size();
+                    right.t.borrow_mut().left = Some(node);
+                    node = right;
+                    let Some(next) = node.t.borrow_mut().right.take() else {
+                        break;
+                    };
+                    lower.push(node);
+                    node = next;
+                } else {
+                    lower.push(node);
+                    node = right;
+                }
+            } else {
+                break;
+            }
+        }
+
+        // Hang the branches of the new root at the inner ends of both trees, then fix
+        // the sizes from the inner ends outwards.
+        let mut left = node.t.borrow_mut().left.take();
+        for n in lower.into_iter().rev() {
+            n.t.borrow_mut().right = left;
+            n.t.borrow_mut().update_size();
+            left = Some(n);
+        }
+        let mut right = node.t.borrow_mut().right.take();
+        for n in upper.into_iter().rev() {
+            n.t.borrow_mut().left = right;
+            n.t.borrow_mut().update_size();
+            right = Some(n);
+        }
+        {
+            let mut trunk = node.t.borrow_mut();
+            trunk.left = left;
+            trunk.right = right;
+            trunk.update_size();
+        }
+        node
+    }
+}
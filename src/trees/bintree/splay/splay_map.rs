@@ -0,0 +1,163 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{iter::Iter, map::map_api, plain::Plain, BinTree},
+        Duplicates,
+    },
+};
+
+/// A splay tree behind a handle that owns its root.
+///
+/// Finding, inserting and removing a key splay it to the root, so a small set of hot
+/// keys stays near the top. Lookups restructure the tree too, which is why the root
+/// sits in a `RefCell` and `get` still works through `&self`. Iterating leaves the
+/// tree as it is.
+pub struct SplayMap<K: Ord + Copy, V> {
+    root: RefCell<Option<BinTree<V, K, ()>>>,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> SplayMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            root: RefCell::new(None),
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Takes over an existing tree.
+    pub fn from_root(root: BinTree<V, K, ()>) -> Self {
+        Self {
+            root: RefCell::new(Some(root)),
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, ()>> {
+        self.root.borrow_mut().take()
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, ()>> {
+        self.root
+            .borrow()
+            .as_ref()
+            .map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // Splays `key` to the root and tells whether it is there.
+    fn splay(&self, key: K) -> bool {
+        let mut root = self.root.borrow_mut();
+        match root.take() {
+            Some(r) => {
+                let r = r.splay(key);
+                let found = r.t.borrow().key == key;
+                *root = Some(r);
+                found
+            }
+            None => false,
+        }
+    }
+
+    // Inserts a key the map does not hold yet. `contains_key` has splayed the last
+    // node on its search path to the root already.
+    fn insert_new(&mut self, key: K, value: V) {
+        // The new node becomes the root, splitting the old one's branches at `key`.
+        let node = BinTree::new(key, value);
+        if let Some(root) = self.root.get_mut().take() {
+            let below = root.t.borrow().key < key;
+            let mut trunk = node.t.borrow_mut();
+            if below {
+                trunk.right = root.t.borrow_mut().right.take();
+                root.t.borrow_mut().update_size();
+                trunk.left = Some(root);
+            } else {
+                trunk.left = root.t.borrow_mut().left.take();
+                root.t.borrow_mut().update_size();
+                trunk.right = Some(root);
+            }
+            trunk.update_size();
+        }
+        *self.root.get_mut() = Some(node);
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        if !self.splay(key) {
+            return Err(TreeError::KeyNotFound);
+        }
+        let root = self.root.get_mut().take().unwrap();
+        let (left, right) = {
+            let mut trunk = root.t.borrow_mut();
+            (trunk.left.take(), trunk.right.take())
+        };
+        // Splaying the left branch for `key` lifts its largest node, which has no right
+        // branch to keep.
+        *self.root.get_mut() = match left {
+            Some(left) => {
+                let left = left.splay(key);
+                left.t.borrow_mut().right = right;
+                left.t.borrow_mut().update_size();
+                Some(left)
+            }
+            None => right,
+        };
+        let value = root.t.borrow().value.clone();
+        Ok(value)
+    }
+
+    /// Looks `key` up and splays it, or the last node on its search path, to the root.
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        if self.splay(*key) {
+            let root = self.root.borrow();
+            let value = root.as_ref().unwrap().t.borrow().value.clone();
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.dismantle();
+    }
+
+    // Splay trees may degenerate into long paths, which dropping node by node would
+    // follow recursively. Take apart the nodes nobody else holds with a stack instead.
+    fn dismantle(&mut self) {
+        let mut stack: Vec<BinTree<V, K, ()>> = self.root.get_mut().take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            if Rc::strong_count(&node.t) == 1 {
+                let mut trunk = node.t.borrow_mut();
+                stack.extend(trunk.left.take());
+                stack.extend(trunk.right.take());
+            }
+        }
+    }
+}
+
+impl<K: Ord + Copy + Debug, V> SplayMap<K, V> {
+    /// Verifies the tree; an empty map is always valid.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        match self.root.borrow().as_ref() {
+            Some(root) => root.check_invariants(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K: Ord + Copy, V> Drop for SplayMap<K, V> {
+    fn drop(&mut self) {
+        self.dismantle();
+    }
+}
+
+impl<K: Ord + Copy, V> Default for SplayMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+map_api!([K: Ord + Copy, V] SplayMap<K, V>, Iter<V, K, ()>);
@@ -0,0 +1,250 @@
+mod rng;
+mod treap_map;
+
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::error::TreeError;
+
+use super::{avl::Pair, trunk::Trunk, BinTree};
+
+pub use self::{
+    rng::{PriorityRng, XorShift},
+    treap_map::TreapMap,
+};
+
+/// The random priority of a treap node. Parents outrank their children, which keeps
+/// the expected depth at O(log n) whatever the order of the keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority(pub u64);
+
+// The three parts of a split: below the key, the detached node at it and above it.
+type Parts<T, K> = (
+    Option<BinTree<T, K, Priority>>,
+    Option<BinTree<T, K, Priority>>,
+    Option<BinTree<T, K, Priority>>,
+);
+
+impl<T, K: Ord + Copy> BinTree<T, K, Priority> {
+    pub fn with_priority(key: K, value: T, priority: Priority) -> Self {
+        Self {
+            t: Rc::new(RefCell::new(Trunk {
+                key,
+                value: Rc::new(RefCell::new(value)),
+                left: None,
+                right: None,
+                state: priority,
+                size: 1,
+                aggregate: (),
+            })),
+        }
+    }
+
+    fn priority(&self) -> Priority {
+        self.t.borrow().state
+    }
+
+    fn split_at(self, key: K) -> Parts<T, K> {
+        let here = self.t.borrow().key;
+        if key < here {
+            let left = self.t.borrow_mut().left.take();
+            let (ll, found, lr) = match left {
+                Some(left) => left.split_at(key),
+                None => (None, None, None),
+            };
+            self.t.borrow_mut().left = lr;
+            self.t.borrow_mut().update_size();
+            (ll, found, Some(self))
+        } else if here < key {
+            let right = self.t.borrow_mut().right.take();
+            let (rl, found, rr) = match right {
+                Some(right) => right.split_at(key),
+                None => (None, None, None),
+            };
+            self.t.borrow_mut().right = rl;
+            self.t.borrow_mut().update_size();
+            (Some(self), found, rr)
+        } else {
+            let (left, right) = {
+                let mut trunk = self.t.borrow_mut();
+                (trunk.left.take(), trunk.right.take())
+            };
+            self.t.borrow_mut().update_size();
+            (left, Some(self), right)
+        }
+    }
+
+    // Merges two treaps whose keys do not interleave, left below right.
+    fn merge_ordered(left: Option<Self>, right: Option<Self>) -> Option<Self> {
+        let (left, right) = match (left, right) {
+            (None, right) => return right,
+            (left, None) => return left,
+            (Some(left), Some(right)) => (left, right),
+        };
+        if right.priority() < left.priority() {
+            let lr = left.t.borrow_mut().right.take();
+            left.t.borrow_mut().right = Self::merge_ordered(lr, Some(right));
+            left.t.borrow_mut().update_size();
+            Some(left)
+        } else {
+            let rl = right.t.borrow_mut().left.take();
+            right.t.borrow_mut().left = Self::merge_ordered(Some(left), rl);
+            right.t.borrow_mut().update_size();
+            Some(right)
+        }
+    }
+
+    /// Splits the treap into the pairs below `key`, the pair at `key` and those above
+    /// it in expected O(log n).
+    pub fn split(self, key: K) -> (Option<Self>, Option<Pair<T, K>>, Option<Self>) {
+        let (left, found, right) = self.split_at(key);
+        let found = found.map(|node| {
+            let trunk = node.t.borrow();
+            (trunk.key, trunk.value.clone())
+        });
+        (left, found, right)
+    }
+
+    /// Merges two treaps in expected O(log n). All keys in `left` must be below those
+    /// in `right`.
+    pub fn merge(left: Option<Self>, right: Option<Self>) -> Option<Self> {
+        assert!(
+            match (left.as_ref(), right.as_ref()) {
+                (Some(l), Some(r)) => l.last().0 < r.first().0,
+                _ => true,
+            },
+            "The keys of the treaps to merge are not in order."
+        );
+        Self::merge_ordered(left, right)
+    }
+
+    /// Merges two treaps whose keys may interleave; on equal keys the values of `other`
+    /// win. Takes expected O(m log(n / m)) for treaps of sizes m <= n.
+    pub fn union(this: Option<Self>, other: Option<Self>) -> Option<Self> {
+        Self::union_with(this, other, &|_, other| other)
+    }
+
+    // Like `union`, but `settle` picks the cell to keep for a key in both treaps from
+    // the one of `this` and the one of `other`.
+    pub(super) fn union_with<F>(this: Option<Self>, other: Option<Self>, settle: &F) -> Option<Self>
+    where
+        F: Fn(Rc<RefCell<T>>, Rc<RefCell<T>>) -> Rc<RefCell<T>>,
+    {
+        let (this, other) = match (this, other) {
+            (None, other) => return other,
+            (this, None) => return this,
+            (Some(this), Some(other)) => (this, other),
+        };
+        // The node of higher priority stays on top and splits the other one.
+        let other_on_top = this.priority() < other.priority();
+        let (top, below) = if other_on_top {
+            (other, this)
+        } else {
+            (this, other)
+        };
+        let (left, right) = {
+            let mut trunk = top.t.borrow_mut();
+            (trunk.left.take(), trunk.right.take())
+        };
+        let (bl, found, br) = below.split_at(top.t.borrow().key);
+        if let Some(found) = found {
+            let (top_value, found_value) = (top.value_cell(), found.value_cell());
+            top.t.borrow_mut().value = if other_on_top {
+                settle(found_value, top_value)
+            } else {
+                settle(top_value, found_value)
+            };
+        }
+        let left = Self::union_in_order(left, bl, other_on_top, settle);
+        let right = Self::union_in_order(right, br, other_on_top, settle);
+        {
+            let mut trunk = top.t.borrow_mut();
+            trunk.left = left;
+            trunk.right = right;
+            trunk.update_size();
+        }
+        Some(top)
+    }
+
+    // Keeps `other` on the second position, where `settle` expects it.
+    fn union_in_order<F>(
+        top: Option<Self>,
+        below: Option<Self>,
+        top_is_other: bool,
+        settle: &F,
+    ) -> Option<Self>
+    where
+        F: Fn(Rc<RefCell<T>>, Rc<RefCell<T>>) -> Rc<RefCell<T>>,
+    {
+        if top_is_other {
+            Self::union_with(below, top, settle)
+        } else {
+            Self::union_with(top, below, settle)
+        }
+    }
+
+    fn value_cell(&self) -> Rc<RefCell<T>> {
+        self.t.borrow().value.clone()
+    }
+
+    /// Inserts the pair with the given priority by splitting at `key` and merging the
+    /// new node in between. Panics on a duplicate key.
+    pub fn insert(self, key: K, value: T, priority: Priority) -> Self {
+        match self.try_insert(key, value, priority) {
+            (Ok(()), root) => root,
+            (Err(e), _) => panic!("{}", e),
+        }
+    }
+
+    /// Like `insert`, but reports `TreeError::DuplicateKey` instead of panicking. The
+    /// parts of the split are then merged back around the node found at `key`.
+    pub fn try_insert(self, key: K, value: T, priority: Priority) -> (Result<(), TreeError>, Self) {
+        let (left, found, right) = self.split_at(key);
+        let (result, node) = match found {
+            Some(found) => (Err(TreeError::DuplicateKey), found),
+            None => (Ok(()), Self::with_priority(key, value, priority)),
+        };
+        let root = Self::merge_ordered(Self::merge_ordered(left, Some(node)), right);
+        (result, root.unwrap())
+    }
+
+    /// Removes `key` by merging the branches below it. Returns whether it was found
+    /// and the remaining treap, if any.
+    pub fn delete(self, key: K) -> (bool, Option<Self>) {
+        let (left, found, right) = self.split_at(key);
+        (found.is_some(), Self::merge_ordered(left, right))
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<T>>> {
+        self.find_node(*key).map(|node| node.value_cell())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find_node(*key).is_some()
+    }
+}
+
+impl<T, K: Ord + Copy + Debug> BinTree<T, K, Priority> {
+    /// Verifies the key order, the subtree sizes and that no node outranks its parent,
+    /// reporting the first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        self.check_with(|_, _, _, _| Ok(()))?;
+        let mut stack = vec![Self { t: self.t.clone() }];
+        while let Some(node) = stack.pop() {
+            let trunk = node.t.borrow();
+            for child in [trunk.left.as_ref(), trunk.right.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                if trunk.state < child.priority() {
+                    return Err(TreeError::CorruptStructure(format!(
+                        "node {:?} outranks its parent {:?}",
+                        child.t.borrow().key,
+                        trunk.key
+                    )));
+                }
+                stack.push(Self { t: child.t.clone() });
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A source of treap priorities. Any generator can be plugged in; seeding it makes the
+/// shape of a treap reproducible.
+pub trait PriorityRng {
+    fn next_u64(&mut self) -> u64;
+
+    /// A generator for another treap, seeded from this one so that the two do not draw
+    /// the same priorities.
+    fn fork(&mut self) -> Self
+    where
+        Self: Sized;
+}
+
+/// A xorshift64* generator, small and fast enough for priorities.
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // The all-zero state would never leave zero.
+        Self {
+            state: if seed == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Seeds the generator from the per-process randomness of the standard hasher.
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl PriorityRng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+}
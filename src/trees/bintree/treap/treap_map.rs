@@ -0,0 +1,135 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{
+    error::TreeError,
+    trees::{
+        bintree::{iter::Iter, map::map_api, BinTree},
+        Duplicates,
+    },
+};
+
+use super::{Priority, PriorityRng, XorShift};
+
+/// A treap behind a handle that owns its root and the generator of its priorities.
+pub struct TreapMap<K: Ord + Copy, V, R: PriorityRng = XorShift> {
+    root: Option<BinTree<V, K, Priority>>,
+    rng: R,
+    dup: Duplicates<V>,
+}
+
+impl<K: Ord + Copy, V> TreapMap<K, V> {
+    /// An empty treap drawing its priorities from a randomly seeded `XorShift`.
+    pub fn new() -> Self {
+        Self::with_rng(XorShift::from_entropy())
+    }
+}
+
+impl<K: Ord + Copy, V, R: PriorityRng> TreapMap<K, V, R> {
+    /// An empty treap drawing its priorities from `rng`; seed it for reproducible shapes.
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            root: None,
+            rng,
+            dup: Duplicates::Overwrite,
+        }
+    }
+
+    /// Hands the tree back, or `None` if the map is empty.
+    pub fn into_root(self) -> Option<BinTree<V, K, Priority>> {
+        self.root
+    }
+
+    // A handle to the root, for the methods `map_api!` adds.
+    fn tree(&self) -> Option<BinTree<V, K, Priority>> {
+        self.root.as_ref().map(|root| BinTree { t: root.t.clone() })
+    }
+
+    // Inserts a key the map does not hold yet.
+    fn insert_new(&mut self, key: K, value: V) {
+        let priority = Priority(self.rng.next_u64());
+        self.root = Some(match self.root.take() {
+            Some(root) => root.insert(key, value, priority),
+            None => BinTree::with_priority(key, value, priority),
+        });
+    }
+
+    /// Removes `key` and returns its value.
+    pub fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError> {
+        let value = self.get(&key).ok_or(TreeError::KeyNotFound)?;
+        let (_, root) = self.root.take().unwrap().delete(key);
+        self.root = root;
+        Ok(value)
+    }
+
+    /// Moves the pairs from `key` on into a new map with the same duplicates policy and
+    /// a generator forked from this one.
+    pub fn split_off(&mut self, key: K) -> Self {
+        let (left, found, right) = match self.root.take() {
+            Some(root) => root.split_at(key),
+            None => (None, None, None),
+        };
+        self.root = left;
+        Self {
+            root: BinTree::merge_ordered(found, right),
+            rng: self.rng.fork(),
+            dup: self.dup,
+        }
+    }
+
+    /// Moves all pairs of `other` into the map, settling equal keys by the duplicates
+    /// policy of the map. Under `Reject` a key in both maps leaves them untouched and
+    /// reports `TreeError::DuplicateKey`. A merge goes into the cell of this map and
+    /// clones the value of `other` if its cell is still shared.
+    pub fn append(&mut self, other: &mut Self) -> Result<(), TreeError>
+    where
+        V: Clone,
+    {
+        if let Duplicates::Reject = self.dup {
+            let (small, large) = if self.len() <= other.len() {
+                (&*self, &*other)
+            } else {
+                (&*other, &*self)
+            };
+            if small.iter().any(|(k, _)| large.contains_key(&k)) {
+                return Err(TreeError::DuplicateKey);
+            }
+        }
+        let (this, that) = (self.root.take(), other.root.take());
+        self.root = match self.dup {
+            Duplicates::Reject | Duplicates::Overwrite => BinTree::union(this, that),
+            Duplicates::Merge(merge) => BinTree::union_with(this, that, &|kept, theirs| {
+                let value = Rc::try_unwrap(theirs)
+                    .map_or_else(|theirs| theirs.borrow().clone(), RefCell::into_inner);
+                merge(&mut kept.borrow_mut(), value);
+                kept
+            }),
+        };
+        Ok(())
+    }
+
+    pub fn get(&self, key: &K) -> Option<Rc<RefCell<V>>> {
+        self.root.as_ref().and_then(|root| root.get(key))
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+}
+
+impl<K: Ord + Copy + Debug, V, R: PriorityRng> TreapMap<K, V, R> {
+    /// Verifies the tree; an empty map is always valid.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        match self.root.as_ref() {
+            Some(root) => root.check_invariants(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K: Ord + Copy, V> Default for TreapMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+map_api!([K: Ord + Copy, V, R: PriorityRng] TreapMap<K, V, R>, Iter<V, K, Priority>);
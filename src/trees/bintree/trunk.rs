@@ -1,46 +1,158 @@
-use super::BinTree;
+use std::{cell::RefCell, mem, rc::Rc};
+
+use crate::{error::TreeError, trees::Duplicates};
+
+use super::{aggregate::Aggregate, BinTree};
 
 #[derive(Debug)]
-pub(super) struct Trunk<T, K: Ord + Copy, S> {
+pub(super) struct Trunk<T, K: Ord + Copy, S, A = ()> {
     pub(super) key: K,
-    pub(super) value: T,
-    pub(super) right: Option<BinTree<T, K, S>>,
-    pub(super) left: Option<BinTree<T, K, S>>,
+    pub(super) value: Rc<RefCell<T>>,
+    pub(super) right: Option<BinTree<T, K, S, A>>,
+    pub(super) left: Option<BinTree<T, K, S, A>>,
     pub(super) state: S,
+    /// The number of nodes in the subtree rooted here, including itself.
+    pub(super) size: usize,
+    /// The summary of the values in the subtree rooted here.
+    pub(super) aggregate: A,
+}
+
+impl<T, K: Ord + Copy, S, A> Trunk<T, K, S, A> {
+    pub(super) fn update_size(&mut self) {
+        let left = self.left.as_ref().map_or(0, |l| l.t.borrow().size);
+        let right = self.right.as_ref().map_or(0, |r| r.t.borrow().size);
+        self.size = 1 + left + right;
+    }
+}
+
+impl<T, K: Ord + Copy, S, A: Aggregate<T>> Trunk<T, K, S, A> {
+    // Recomputes the size and the summary from those of the branches.
+    pub(super) fn update(&mut self) {
+        self.update_size();
+        let own = A::of(&self.value.borrow());
+        let lower = match self.left.as_ref() {
+            Some(l) => l.t.borrow().aggregate.combine(&own),
+            None => own,
+        };
+        self.aggregate = match self.right.as_ref() {
+            Some(r) => lower.combine(&r.t.borrow().aggregate),
+            None => lower,
+        };
+    }
+}
+
+impl<T, K: Ord + Copy, S, A: Aggregate<T>> BinTree<T, K, S, A> {
+    // Settles an insert under a key that is already in the tree by `dup`. An overwrite
+    // puts the value into a new cell and hands back the old one; a merge changes the
+    // stored value in place and hands back its cell.
+    pub(super) fn replace_value(
+        &self,
+        key: K,
+        value: T,
+        dup: Duplicates<T>,
+    ) -> Result<Rc<RefCell<T>>, TreeError> {
+        let path = self.path_to(key).ok_or(TreeError::KeyNotFound)?;
+        let node = path.last().unwrap();
+        let cell = match dup {
+            Duplicates::Reject => return Err(TreeError::DuplicateKey),
+            Duplicates::Overwrite => {
+                mem::replace(&mut node.t.borrow_mut().value, Rc::new(RefCell::new(value)))
+            }
+            Duplicates::Merge(merge) => {
+                let cell = node.t.borrow().value.clone();
+                merge(&mut cell.borrow_mut(), value);
+                cell
+            }
+        };
+        for node in path.iter().rev() {
+            node.t.borrow_mut().update();
+        }
+        Ok(cell)
+    }
+
+    // Recomputes the nodes on the path to `key`, bottom up. Returns whether the key
+    // was found.
+    pub(super) fn update_path(&self, key: K) -> bool {
+        let Some(path) = self.path_to(key) else {
+            return false;
+        };
+        for node in path.iter().rev() {
+            node.t.borrow_mut().update();
+        }
+        true
+    }
 }
 
-impl<T, K: Ord + Copy, S> BinTree<T, K, S> {
-    pub(super) fn find_parent(&self, child_key: K) -> (Option<Self>, Self, i8) {
+impl<T, K: Ord + Copy, S, A> BinTree<T, K, S, A> {
+    // The nodes from here down to the one holding `key`, if it is there.
+    pub(super) fn path_to(&self, key: K) -> Option<Vec<Self>> {
+        let mut path = vec![Self { t: self.t.clone() }];
+        loop {
+            let next = {
+                let trunk = path.last().unwrap().t.borrow();
+                let child = if key < trunk.key {
+                    trunk.left.as_ref()
+                } else if trunk.key < key {
+                    trunk.right.as_ref()
+                } else {
+                    break;
+                };
+                child.map(|c| Self { t: c.t.clone() })
+            };
+            path.push(next?);
+        }
+        Some(path)
+    }
+
+    pub(super) fn find_node(&self, key: K) -> Option<Self> {
+        let mut node = Self { t: self.t.clone() };
+        loop {
+            let next = {
+                let trunk = node.t.borrow();
+                if key < trunk.key {
+                    trunk.left.as_ref().map(|l| Self { t: l.t.clone() })
+                } else if trunk.key < key {
+                    trunk.right.as_ref().map(|r| Self { t: r.t.clone() })
+                } else {
+                    break;
+                }
+            };
+            node = next?;
+        }
+        Some(node)
+    }
+
+    pub(super) fn find_parent(&self, child_key: K) -> Result<(Option<Self>, Self, i8), TreeError> {
         if self.t.borrow().key < child_key {
             if let Some(ref right) = self.t.borrow().right {
                 if right.t.borrow().key == child_key {
-                    (
+                    Ok((
                         Some(Self { t: self.t.clone() }),
                         Self { t: right.t.clone() },
                         1,
-                    )
+                    ))
                 } else {
                     Self::find_parent(right, child_key)
                 }
             } else {
-                panic!("Failed to find the key.")
+                Err(TreeError::KeyNotFound)
             }
         } else if child_key < self.t.borrow().key {
             if let Some(ref left) = self.t.borrow().left {
                 if left.t.borrow().key == child_key {
-                    (
+                    Ok((
                         Some(Self { t: self.t.clone() }),
                         Self { t: left.t.clone() },
                         -1,
-                    )
+                    ))
                 } else {
                     Self::find_parent(left, child_key)
                 }
             } else {
-                panic!("Failed to find the key.")
+                Err(TreeError::KeyNotFound)
             }
         } else {
-            (None, Self { t: self.t.clone() }, 0)
+            Ok((None, Self { t: self.t.clone() }, 0))
         }
     }
 }
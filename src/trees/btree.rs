@@ -1,22 +1,63 @@
 mod branch;
+mod build;
+mod check;
+pub mod cursor;
+pub mod iter;
+mod order;
+pub mod paged;
+pub mod snapshot;
 mod trunk;
 
 use std::{
-    cell::RefCell,
-    rc::Rc,
+    cell::{Cell, OnceCell, RefCell},
+    collections::BTreeSet,
+    mem,
+    ops::RangeBounds,
+    rc::{Rc, Weak},
 };
 
+use crate::{
+    error::TreeError,
+    trees::{map::OrderedMap, Duplicates},
+};
+
+use self::iter::Iter;
+
 #[derive(Debug)]
 pub struct Trunk<T, K: Ord> {
     keys: Vec<K>,
     vals: Vec<BTree<T, K>>,
     upbd: K,
+    epoch: u64,
+    shared: Rc<Shared<T>>,
 }
 
+/// A leaf. Leaves are linked to their neighbours, so that scans walk from leaf to leaf
+/// without going back through the trunks; the links are weak, the trunks own the leaves.
 #[derive(Debug)]
 pub struct Branch<T, K: Ord> {
     keys: Vec<K>,
     vals: Vec<Rc<RefCell<T>>>,
+    prev: Weak<RefCell<Branch<T, K>>>,
+    next: Weak<RefCell<Branch<T, K>>>,
+    epoch: u64,
+    // The epoch in which the leaf left the tree, u64::MAX while it is in there.
+    retired: Cell<u64>,
+    shared: Rc<Shared<T>>,
+}
+
+// What the nodes of one tree have in common. Each of them points here, so that the
+// root, whichever node it is, knows it.
+#[derive(Debug)]
+struct Shared<T> {
+    dup: Cell<Duplicates<T>>,
+    // Copies a value, so that the tree changes copies of the values a snapshot holds.
+    // Set by the first snapshot, which requires values to be `Clone`.
+    copy: OnceCell<fn(&T) -> T>,
+    // The epoch new nodes are stamped with. Each snapshot closes one.
+    clock: Cell<u64>,
+    // The epochs of the snapshots still alive.
+    live: RefCell<BTreeSet<u64>>,
 }
 
 #[derive(Debug)]
@@ -43,6 +84,29 @@ impl <T, K> Branch<BTree<T, K>, K> {
 
  */
 
+impl<T> Shared<T> {
+    fn new() -> Rc<Self> {
+        Rc::new(Self {
+            dup: Cell::new(Duplicates::Overwrite),
+            copy: OnceCell::new(),
+            clock: Cell::new(0),
+            live: RefCell::new(BTreeSet::new()),
+        })
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.get()
+    }
+
+    // Whether a snapshot still holds the nodes stamped with the epoch.
+    fn frozen(&self, epoch: u64) -> bool {
+        self.live
+            .borrow()
+            .last()
+            .is_some_and(|newest| epoch <= *newest)
+    }
+}
+
 impl<T, K: Ord + Copy> BTree<T, K> {
     fn unwrap_tr(&self) -> &Rc<RefCell<Trunk<T, K>>> {
         match self {
@@ -58,18 +122,42 @@ impl<T, K: Ord + Copy> BTree<T, K> {
         }
     }
 
+    // The largest key of a subtree other than an empty root leaf.
+    fn upbd(&self) -> K {
+        match self {
+            BTree::Tr(tr) => tr.borrow().upbd,
+            BTree::Br(br) => br.borrow().upbd(),
+        }
+    }
+
+    fn shared(&self) -> Rc<Shared<T>> {
+        match self {
+            BTree::Tr(tr) => tr.borrow().shared.clone(),
+            BTree::Br(br) => br.borrow().shared.clone(),
+        }
+    }
+
+    /// An empty tree of order `max`. Inserting a key again overwrites its value, as in
+    /// every map of the crate, until `with_duplicates` sets another policy.
     pub fn new(max: usize) -> Self {
-        Self::Br(Rc::new(RefCell::new(Branch {
-            keys: Vec::with_capacity(max + 1),
-            vals: Vec::with_capacity(max + 1),
-        })))
+        Self::Br(Rc::new(RefCell::new(Branch::new(
+            Vec::with_capacity(max + 1),
+            Vec::with_capacity(max + 1),
+            Shared::new(),
+        ))))
     }
 
-    pub fn find(&self, key: K) -> Result<Rc<RefCell<T>>, String> {
+    /// Sets how inserts treat a key that is already in the tree.
+    pub fn with_duplicates(self, dup: Duplicates<T>) -> Self {
+        self.shared().dup.set(dup);
+        self
+    }
+
+    pub fn find(&self, key: K) -> Result<Rc<RefCell<T>>, TreeError> {
         match self {
             BTree::Br(ref br) => match br.borrow().keys.binary_search(&key) {
                 Ok(pos) => Ok(br.borrow().vals[pos].clone()),
-                Err(_) => Err("Not found".to_string()),
+                Err(_) => Err(TreeError::KeyNotFound),
             },
             BTree::Tr(ref tr) => {
                 let pos = match tr.borrow().keys.binary_search(&key) {
@@ -81,35 +169,76 @@ impl<T, K: Ord + Copy> BTree<T, K> {
         }
     }
 
-    pub fn insert(&mut self, key: K, value: T) {
+    /// Inserts `value` and returns the cell `key` held before, if the policy set by
+    /// `with_duplicates` lets the insert replace it. Under `Merge` it returns the cell
+    /// that holds the merged value.
+    pub fn insert(&mut self, key: K, value: T) -> Option<Rc<RefCell<T>>> {
+        match self.try_insert(key, value) {
+            Ok(old) => old,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `insert`, but reports a rejected key as `TreeError::DuplicateKey`.
+    pub fn try_insert(&mut self, key: K, value: T) -> Result<Option<Rc<RefCell<T>>>, TreeError> {
+        let old = match self.find(key) {
+            Ok(old) => old,
+            Err(_) => return self.insert_new(key, value).map(|_| None),
+        };
+        let merge = match self.shared().dup.get() {
+            Duplicates::Reject => return Err(TreeError::DuplicateKey),
+            Duplicates::Overwrite => {
+                return Ok(Some(self.replace(key, Rc::new(RefCell::new(value)))));
+            }
+            Duplicates::Merge(merge) => merge,
+        };
+        let (leaf, _) = self.seek(|k| k < &key, |k| k < &key);
+        if !leaf.borrow().frozen() {
+            merge(&mut old.borrow_mut(), value);
+            return Ok(Some(old));
+        }
+        // A snapshot holds the value, so the merge goes into a copy of it in a new cell.
+        let shared = self.shared();
+        let copy = shared.copy.get().expect("Only snapshots freeze leaves.");
+        let mut merged = copy(&old.borrow());
+        merge(&mut merged, value);
+        let cell = Rc::new(RefCell::new(merged));
+        self.replace(key, cell.clone());
+        Ok(Some(cell))
+    }
+
+    // Inserts a key the tree does not hold yet.
+    fn insert_new(&mut self, key: K, value: T) -> Result<(), TreeError> {
+        self.own();
         match self {
             Self::Br(br) => {
-                let right_br = br.borrow_mut().insert(key, value);
+                let right_br = br.borrow_mut().insert(key, value)?;
                 if let Some(right_br) = right_br {
                     let mut keys = Vec::with_capacity(right_br.keys.capacity());
                     let mut vals = Vec::with_capacity(right_br.vals.capacity());
                     let upbd = right_br.upbd();
+                    let shared = right_br.shared.clone();
+                    let right_br = Rc::new(RefCell::new(right_br));
+                    Branch::link_after(br, &right_br);
                     keys.push(*br.borrow().keys.iter().max().unwrap());
-                    vals.extend([
-                        Self::Br(br.clone()),
-                        Self::Br(Rc::new(RefCell::new(right_br))),
-                    ]);
-                    *self = Self::Tr(Rc::new(RefCell::new(Trunk { keys, vals, upbd })));
+                    vals.extend([Self::Br(br.clone()), Self::Br(right_br)]);
+                    *self = Self::Tr(Rc::new(RefCell::new(Trunk::new(keys, vals, upbd, shared))));
                 }
             }
             Self::Tr(tr) => {
-                let right_tr = tr.borrow_mut().insert(key, value);
+                let right_tr = tr.borrow_mut().insert(key, value)?;
                 if let Some(right_tr) = right_tr {
                     let mut keys = Vec::with_capacity(right_tr.keys.capacity());
                     let mut vals = Vec::with_capacity(right_tr.vals.capacity());
                     let upbd = right_tr.upbd;
+                    let shared = right_tr.shared.clone();
                     keys.push(tr.borrow().upbd);
                     vals.extend([
                         Self::Tr(tr.clone()),
                         Self::Tr(Rc::new(RefCell::new(right_tr))),
                     ]);
 
-                    *self = Self::Tr(Rc::new(RefCell::new(Trunk { keys, vals, upbd })));
+                    *self = Self::Tr(Rc::new(RefCell::new(Trunk::new(keys, vals, upbd, shared))));
                 } else {
                     if tr.borrow().upbd < key {
                         tr.borrow_mut().upbd = key;
@@ -117,24 +246,89 @@ impl<T, K: Ord + Copy> BTree<T, K> {
                 }
             }
         }
+        Ok(())
     }
 
-    pub fn delete(&mut self, key: K) -> bool {
+    // Puts the cell in place of the one stored under `key`, which is in the tree. The
+    // leaf is copied first if a snapshot holds it, so the snapshot keeps the old cell.
+    fn replace(&mut self, key: K, cell: Rc<RefCell<T>>) -> Rc<RefCell<T>> {
+        self.own();
         match self {
             BTree::Br(br) => {
-                return br.borrow_mut().delete(key);
+                let mut br = br.borrow_mut();
+                let pos = br.keys.binary_search(&key).unwrap();
+                mem::replace(&mut br.vals[pos], cell)
             }
             BTree::Tr(tr) => {
-                let found = tr.borrow_mut().delete(key);
-                if !found {
-                    return false;
-                }
-                if tr.borrow().keys.len() == 0 {
+                let mut tr = tr.borrow_mut();
+                let pos = match tr.keys.binary_search(&key) {
+                    Ok(pos) => pos,
+                    Err(pos) => pos,
+                };
+                tr.vals[pos].replace(key, cell)
+            }
+        }
+    }
+
+    pub fn delete(&mut self, key: K) -> bool {
+        match self.try_delete(key) {
+            Ok(()) => true,
+            Err(TreeError::KeyNotFound) => false,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_delete(&mut self, key: K) -> Result<(), TreeError> {
+        self.own();
+        match self {
+            BTree::Br(br) => br.borrow_mut().delete(key),
+            BTree::Tr(tr) => {
+                tr.borrow_mut().delete(key)?;
+                if tr.borrow().keys.is_empty() {
                     let it = tr.borrow_mut().vals.pop().unwrap();
                     *self = it;
                 }
-                return true;
+                Ok(())
             }
         }
     }
 }
+
+impl<T, K: Ord + Copy> OrderedMap<K, T> for BTree<T, K> {
+    type Iter<'a> = Iter<'a, T, K>
+    where
+        Self: 'a;
+
+    fn insert(&mut self, key: K, value: T) -> Option<Rc<RefCell<T>>> {
+        BTree::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: K) -> Result<Rc<RefCell<T>>, TreeError> {
+        let value = self.find(key)?;
+        self.try_delete(key).map(|_| value)
+    }
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<T>>> {
+        self.find(*key).ok()
+    }
+
+    fn len(&self) -> usize {
+        BTree::len(self)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTree::iter(self)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Iter<'_> {
+        BTree::range(self, range)
+    }
+
+    fn first(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        BTree::first(self)
+    }
+
+    fn last(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        BTree::last(self)
+    }
+}
@@ -1,8 +1,98 @@
-use std::{rc::Rc, cell::RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    mem,
+    rc::{Rc, Weak},
+};
 
-use super::Branch;
+use crate::error::TreeError;
+
+use super::{Branch, Shared};
 
 impl<T, K: Ord + Copy> Branch<T, K> {
+    // A leaf not linked to any other yet.
+    pub(super) fn new(keys: Vec<K>, vals: Vec<Rc<RefCell<T>>>, shared: Rc<Shared<T>>) -> Self {
+        Self {
+            keys,
+            vals,
+            prev: Weak::new(),
+            next: Weak::new(),
+            epoch: shared.now(),
+            retired: Cell::new(u64::MAX),
+            shared,
+        }
+    }
+
+    // Whether a snapshot holds the leaf, which must then stay as it is, links included.
+    pub(super) fn frozen(&self) -> bool {
+        self.shared.frozen(self.epoch)
+    }
+
+    // Whether the leaf is in the tree as the snapshot of `epoch` sees it, or as it is
+    // now for None.
+    pub(super) fn visible(&self, epoch: Option<u64>) -> bool {
+        match epoch {
+            None => self.retired.get() == u64::MAX,
+            Some(epoch) => self.epoch <= epoch && epoch < self.retired.get(),
+        }
+    }
+
+    // Whether next follows prev by links both ways.
+    //
+    // A link is only ever set to the neighbour a leaf has at the time, but the tree does
+    // not touch frozen leaves, whose links go stale as it copies or merges the leaves
+    // around them. So a link counts only where it is returned: both ends get relinked
+    // together, which a stale link never is.
+    pub(super) fn linked(prev: &Rc<RefCell<Self>>, next: &Rc<RefCell<Self>>) -> bool {
+        Weak::as_ptr(&prev.borrow().next) == Rc::as_ptr(next)
+            && Weak::as_ptr(&next.borrow().prev) == Rc::as_ptr(prev)
+    }
+
+    // Links the detached leaf right into the list of leaves, right after left.
+    pub(super) fn link_after(left: &Rc<RefCell<Self>>, right: &Rc<RefCell<Self>>) {
+        let next = left.borrow().next.upgrade();
+        if let Some(next) = next.filter(|next| Self::linked(left, next)) {
+            if !next.borrow().frozen() {
+                next.borrow_mut().prev = Rc::downgrade(right);
+            }
+        }
+        let mut r = right.borrow_mut();
+        r.prev = Rc::downgrade(left);
+        r.next = mem::replace(&mut left.borrow_mut().next, Rc::downgrade(right));
+    }
+
+    // Puts the copy in the place of the leaf between its neighbours, and retires the leaf.
+    pub(super) fn supersede(leaf: &Rc<RefCell<Self>>, copy: &Rc<RefCell<Self>>) {
+        let (prev, next) = {
+            let l = leaf.borrow();
+            (l.prev.upgrade(), l.next.upgrade())
+        };
+        if let Some(p) = prev.filter(|p| Self::linked(p, leaf) && !p.borrow().frozen()) {
+            p.borrow_mut().next = Rc::downgrade(copy);
+        }
+        if let Some(n) = next.filter(|n| Self::linked(leaf, n) && !n.borrow().frozen()) {
+            n.borrow_mut().prev = Rc::downgrade(copy);
+        }
+        leaf.borrow().retire();
+    }
+
+    // Takes upper out of the list of leaves once it is merged into lower, the leaf
+    // before it, and retires it.
+    pub(super) fn unlink(lower: &Rc<RefCell<Self>>, upper: &Rc<RefCell<Self>>) {
+        let next = upper.borrow().next.upgrade();
+        let next = next.filter(|next| Self::linked(upper, next));
+        if Self::linked(lower, upper) {
+            lower.borrow_mut().next = next.as_ref().map_or_else(Weak::new, Rc::downgrade);
+            if let Some(n) = next.filter(|n| !n.borrow().frozen()) {
+                n.borrow_mut().prev = Rc::downgrade(lower);
+            }
+        }
+        upper.borrow().retire();
+    }
+
+    fn retire(&self) {
+        self.retired.set(self.shared.now());
+    }
+
     pub(super) fn min_size(&self) -> usize {
         self.max_size() / 2 + self.max_size() % 2
     }
@@ -15,9 +105,9 @@ impl<T, K: Ord + Copy> Branch<T, K> {
         *self.keys.last().unwrap()
     }
 
-    pub(super) fn insert(&mut self, key: K, value: T) -> Option<Self> {
+    pub(super) fn insert(&mut self, key: K, value: T) -> Result<Option<Self>, TreeError> {
         let pos = match self.keys.binary_search(&key) {
-            Ok(_) => panic!("The key already exists."),
+            Ok(_) => return Err(TreeError::DuplicateKey),
             Err(pos) => pos,
         };
         self.keys.insert(pos, key);
@@ -28,21 +118,21 @@ impl<T, K: Ord + Copy> Branch<T, K> {
             let mut values = Vec::with_capacity(self.vals.capacity());
             keys.extend(self.keys.split_off(self.keys.capacity() / 2));
             values.extend(self.vals.split_off(self.vals.capacity() / 2));
-            Some(Self { keys, vals: values })
+            Ok(Some(Self::new(keys, values, self.shared.clone())))
         } else {
-            None
+            Ok(None)
         }
     }
 
-    pub(super) fn delete(&mut self, key: K) -> bool {
+    pub(super) fn delete(&mut self, key: K) -> Result<(), TreeError> {
         let found = self.keys.binary_search(&key);
         match found {
             Ok(pos) => {
                 self.keys.remove(pos);
                 self.vals.remove(pos);
-                true
+                Ok(())
             }
-            Err(_) => false,
+            Err(_) => Err(TreeError::KeyNotFound),
         }
     }
 }
@@ -0,0 +1,103 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{error::TreeError, trees::check_sorted};
+
+use super::{BTree, Branch, Shared, Trunk};
+
+// Cuts n items into runs of `per`, evening out a short last run with its
+// neighbour so that every run holds between `min` and `max` items.
+fn run_lengths(n: usize, per: usize, min: usize, max: usize) -> Vec<usize> {
+    let mut runs = vec![per; n / per];
+    let rest = n % per;
+    if rest == 0 {
+        return runs;
+    }
+    match runs.pop() {
+        Some(prev) if rest < min && prev + rest <= max => runs.push(prev + rest),
+        Some(prev) if rest < min => {
+            let total = prev + rest;
+            runs.extend([total / 2, total - total / 2]);
+        }
+        Some(prev) => runs.extend([prev, rest]),
+        None => runs.push(rest),
+    }
+    runs
+}
+
+impl<T, K: Ord + Copy> BTree<T, K> {
+    /// Builds a B-tree from pairs in ascending key order in O(n).
+    ///
+    /// Each leaf is filled up to `fill` times `max` keys, but never below the minimal
+    /// occupancy; `fill = 1.0` packs the leaves fully and leaves no room for inserts.
+    /// The trunks above them are packed fully.
+    pub fn from_sorted_iter<I>(iter: I, max: usize, fill: f64) -> Result<Self, TreeError>
+    where
+        I: IntoIterator<Item = (K, T)>,
+    {
+        assert!(
+            0.0 < fill && fill <= 1.0,
+            "The fill factor must be in (0, 1]."
+        );
+        let items: Vec<(K, T)> = iter.into_iter().collect();
+        check_sorted(&items)?;
+        if items.is_empty() {
+            return Ok(Self::new(max));
+        }
+
+        let shared = Shared::new();
+        let min = max / 2 + max % 2;
+        let per = ((max as f64 * fill).ceil() as usize).clamp(min, max);
+        let mut items = items.into_iter();
+        let mut level: Vec<(K, Self)> = run_lengths(items.len(), per, min, max)
+            .into_iter()
+            .map(|len| {
+                let mut keys = Vec::with_capacity(max + 1);
+                let mut vals = Vec::with_capacity(max + 1);
+                for (key, value) in items.by_ref().take(len) {
+                    keys.push(key);
+                    vals.push(Rc::new(RefCell::new(value)));
+                }
+                let upbd = *keys.last().unwrap();
+                (
+                    upbd,
+                    Self::Br(Rc::new(RefCell::new(Branch::new(
+                        keys,
+                        vals,
+                        shared.clone(),
+                    )))),
+                )
+            })
+            .collect();
+        for pair in level.windows(2) {
+            Branch::link_after(pair[0].1.unwrap_br(), pair[1].1.unwrap_br());
+        }
+
+        // A trunk holds between two and max + 1 children. Each child is bounded by
+        // the separator on its right; the last one by the upbd of the trunk.
+        while level.len() > 1 {
+            let mut children = level.into_iter();
+            level = run_lengths(children.len(), max, min.max(2), max + 1)
+                .into_iter()
+                .map(|len| {
+                    let mut keys = Vec::with_capacity(max + 1);
+                    let mut vals = Vec::with_capacity(max + 1);
+                    for (upbd, child) in children.by_ref().take(len) {
+                        keys.push(upbd);
+                        vals.push(child);
+                    }
+                    let upbd = keys.pop().unwrap();
+                    (
+                        upbd,
+                        Self::Tr(Rc::new(RefCell::new(Trunk::new(
+                            keys,
+                            vals,
+                            upbd,
+                            shared.clone(),
+                        )))),
+                    )
+                })
+                .collect();
+        }
+        Ok(level.pop().unwrap().1)
+    }
+}
@@ -0,0 +1,203 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::error::TreeError;
+
+use super::{BTree, Branch};
+
+fn corrupt(report: String) -> TreeError {
+    TreeError::CorruptStructure(report)
+}
+
+// What the walk over the leaves has seen so far.
+struct Leaves<T, K: Ord> {
+    depth: Option<usize>,
+    last: Option<Rc<RefCell<Branch<T, K>>>>,
+    since_snapshot: bool,
+}
+
+impl<T, K: Ord + Copy + Debug> BTree<T, K> {
+    /// Verifies the key order, that all nodes share one order and stay within their
+    /// occupancy bounds, that every separator bounds the child below it, that every
+    /// `upbd` is the largest key of its trunk and that all leaves sit at the same
+    /// depth, linked in key order but for the links a snapshot left stale. Reports the
+    /// first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        // Leaves keep their capacity when they split, so the leftmost one tells the order.
+        let max = match self {
+            BTree::Br(br) => br.borrow().max_size(),
+            BTree::Tr(tr) => {
+                let mut node = tr.clone();
+                loop {
+                    let next = match node.borrow().vals[0] {
+                        BTree::Br(ref br) => break br.borrow().max_size(),
+                        BTree::Tr(ref tr) => tr.clone(),
+                    };
+                    node = next;
+                }
+            }
+        };
+        let mut leaves = Leaves {
+            depth: None,
+            last: None,
+            since_snapshot: 0 < self.shared().now(),
+        };
+        self.check_node(None, None, true, max, 0, &mut leaves)?;
+        let next = leaves.last.as_ref().and_then(|last| {
+            let next = last.borrow().next.upgrade()?;
+            let stale = leaves.since_snapshot
+                && !(next.borrow().visible(None) && Branch::linked(last, &next));
+            (!stale).then_some(next)
+        });
+        match next {
+            Some(next) => Err(corrupt(format!(
+                "the last leaf links to {:?}",
+                next.borrow().keys
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // Checks the subtree whose keys must lie in (lower, upper] and returns its largest
+    // key, which only an empty root leaf lacks.
+    fn check_node(
+        &self,
+        lower: Option<K>,
+        upper: Option<K>,
+        is_root: bool,
+        max: usize,
+        depth: usize,
+        leaves: &mut Leaves<T, K>,
+    ) -> Result<Option<K>, TreeError> {
+        let in_bounds = |key: K| lower.is_none_or(|l| l < key) && upper.is_none_or(|u| key <= u);
+        match self {
+            BTree::Br(leaf) => {
+                let br = leaf.borrow();
+                if br.max_size() != max {
+                    return Err(corrupt(format!(
+                        "leaf at depth {} has order {} instead of {}",
+                        depth,
+                        br.max_size(),
+                        max
+                    )));
+                }
+                if br.keys.len() != br.vals.len() {
+                    return Err(corrupt(format!(
+                        "leaf at depth {} holds {} keys but {} values",
+                        depth,
+                        br.keys.len(),
+                        br.vals.len()
+                    )));
+                }
+                if max < br.keys.len() || (!is_root && br.keys.len() < br.min_size()) {
+                    return Err(corrupt(format!(
+                        "leaf {:?} holds {} keys, outside of [{}, {}]",
+                        br.keys,
+                        br.keys.len(),
+                        br.min_size(),
+                        max
+                    )));
+                }
+                if let Some(pair) = br.keys.windows(2).find(|pair| pair[1] <= pair[0]) {
+                    return Err(corrupt(format!(
+                        "keys {:?} and {:?} out of order in a leaf",
+                        pair[0], pair[1]
+                    )));
+                }
+                if let Some(key) = br.keys.iter().find(|key| !in_bounds(**key)) {
+                    return Err(corrupt(format!(
+                        "key {:?} outside of the bounds {:?} and {:?} of its leaf",
+                        key, lower, upper
+                    )));
+                }
+                if let Some(d) = leaves.depth.filter(|d| *d != depth) {
+                    return Err(corrupt(format!("leaves at depths {} and {}", d, depth)));
+                }
+                leaves.depth = Some(depth);
+                let prev = br.prev.upgrade();
+                let linked = match (&leaves.last, &prev) {
+                    (None, None) => true,
+                    (Some(last), Some(prev)) => {
+                        Rc::ptr_eq(last, prev) && Branch::linked(prev, leaf)
+                    }
+                    _ => false,
+                };
+                // Leaves frozen by a snapshot keep their links as the tree changes around
+                // them, so once there was one, only the links returned have to be right.
+                let stale = leaves.since_snapshot
+                    && !prev.is_some_and(|p| p.borrow().visible(None) && Branch::linked(&p, leaf));
+                if !linked && !stale {
+                    return Err(corrupt(format!(
+                        "leaf {:?} is not linked to the leaf before it",
+                        br.keys
+                    )));
+                }
+                let largest = br.keys.last().copied();
+                drop(br);
+                leaves.last = Some(leaf.clone());
+                Ok(largest)
+            }
+            BTree::Tr(tr) => {
+                let tr = tr.borrow();
+                if tr.max_size() != max {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has order {} instead of {}",
+                        tr.keys,
+                        tr.max_size(),
+                        max
+                    )));
+                }
+                if tr.vals.len() != tr.keys.len() + 1 {
+                    return Err(corrupt(format!(
+                        "trunk {:?} holds {} separators but {} children",
+                        tr.keys,
+                        tr.keys.len(),
+                        tr.vals.len()
+                    )));
+                }
+                // A trunk splits once its separators reach max + 1.
+                let min = if is_root { 2 } else { tr.min_size() };
+                if tr.vals.len() < min || max + 1 < tr.vals.len() {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has {} children, outside of [{}, {}]",
+                        tr.keys,
+                        tr.vals.len(),
+                        min,
+                        max + 1
+                    )));
+                }
+                if let Some(pair) = tr.keys.windows(2).find(|pair| pair[1] <= pair[0]) {
+                    return Err(corrupt(format!(
+                        "separators {:?} and {:?} out of order",
+                        pair[0], pair[1]
+                    )));
+                }
+                if let Some(key) = tr.keys.iter().find(|key| !in_bounds(**key)) {
+                    return Err(corrupt(format!(
+                        "separator {:?} outside of the bounds {:?} and {:?} of its trunk",
+                        key, lower, upper
+                    )));
+                }
+                let last = *tr.keys.last().unwrap();
+                if tr.upbd <= last || upper.is_some_and(|u| u < tr.upbd) {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has upbd {:?} outside of ({:?}, {:?}]",
+                        tr.keys, tr.upbd, last, upper
+                    )));
+                }
+                let mut largest = None;
+                for (i, child) in tr.vals.iter().enumerate() {
+                    let lower = if i == 0 { lower } else { Some(tr.keys[i - 1]) };
+                    let upper = Some(tr.keys.get(i).copied().unwrap_or(tr.upbd));
+                    largest = child.check_node(lower, upper, false, max, depth + 1, leaves)?;
+                }
+                if largest != Some(tr.upbd) {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has upbd {:?} but its largest key is {:?}",
+                        tr.keys, tr.upbd, largest
+                    )));
+                }
+                Ok(largest)
+            }
+        }
+    }
+}
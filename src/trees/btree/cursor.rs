@@ -0,0 +1,232 @@
+use std::{cell::RefCell, mem, rc::Rc};
+
+use super::{iter::At, BTree};
+
+/// A cursor over a B-tree that stands on one pair and moves across the leaves.
+///
+/// Besides the pairs there is an empty position, which the cursor reaches by stepping
+/// past either end and leaves by stepping on to the first or the last pair. Stepping
+/// follows the links between the leaves as iterators do and costs O(1); seeking costs
+/// O(log n).
+///
+/// This one only reads the tree; `BTreeCursorMut` can change it as well.
+pub struct BTreeCursor<'a, T, K: Ord> {
+    tree: &'a BTree<T, K>,
+    at: Position<T, K>,
+}
+
+/// A cursor that can also replace or remove the pair it stands on, and so borrows the
+/// tree mutably. It moves as `BTreeCursor` does.
+pub struct BTreeCursorMut<'a, T, K: Ord> {
+    tree: &'a mut BTree<T, K>,
+    at: Position<T, K>,
+}
+
+// Where a cursor stands, None on the empty position. Both cursors move it alike.
+struct Position<T, K: Ord>(Option<At<T, K>>);
+
+impl<T, K: Ord + Copy> Position<T, K> {
+    fn first(tree: &BTree<T, K>) -> Self {
+        let mut at = Self(None);
+        at.move_next(tree);
+        at
+    }
+
+    // Puts the cursor on the pair at pos, or on the first pair of the following leaves
+    // if pos is past the end of its leaf.
+    fn settle(&mut self, tree: &BTree<T, K>, mut at: At<T, K>) {
+        loop {
+            if at.1 < at.0.borrow().keys.len() {
+                self.0 = Some(at);
+                return;
+            }
+            match tree.after(&at.0, None) {
+                Some(next) => at = next,
+                None => {
+                    self.0 = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn seek(&mut self, tree: &BTree<T, K>, key: &K) -> Option<K> {
+        let at = tree.seek(|k| k < key, |k| k < key);
+        self.settle(tree, at);
+        self.key()
+    }
+
+    fn move_next(&mut self, tree: &BTree<T, K>) -> Option<(K, Rc<RefCell<T>>)> {
+        let at = match self.0.take() {
+            Some((br, pos)) => (br, pos + 1),
+            None => tree.seek(|_| false, |_| false),
+        };
+        self.settle(tree, at);
+        self.current()
+    }
+
+    fn move_prev(&mut self, tree: &BTree<T, K>) -> Option<(K, Rc<RefCell<T>>)> {
+        let mut at = match self.0.take() {
+            Some(at) => at,
+            None => tree.seek(|_| true, |_| true),
+        };
+        loop {
+            if 0 < at.1 {
+                at.1 -= 1;
+                self.0 = Some(at);
+                return self.current();
+            }
+            match tree.before(&at.0, None) {
+                Some(prev) => at = prev,
+                None => return None,
+            }
+        }
+    }
+
+    fn key(&self) -> Option<K> {
+        let (br, pos) = self.0.as_ref()?;
+        Some(br.borrow().keys[*pos])
+    }
+
+    fn value(&self) -> Option<Rc<RefCell<T>>> {
+        let (br, pos) = self.0.as_ref()?;
+        Some(br.borrow().vals[*pos].clone())
+    }
+
+    fn current(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.key().zip(self.value())
+    }
+}
+
+impl<'a, T, K: Ord + Copy> BTreeCursor<'a, T, K> {
+    /// Moves to the first pair whose key is greater than or equal to `key`, or to the
+    /// empty position if there is none, and returns the key found.
+    pub fn seek(&mut self, key: &K) -> Option<K> {
+        self.at.seek(self.tree, key)
+    }
+
+    /// Moves to the next pair and returns it. From the last pair this moves to the empty
+    /// position; from there, to the first pair.
+    pub fn move_next(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.move_next(self.tree)
+    }
+
+    /// Moves to the previous pair and returns it. From the first pair this moves to the
+    /// empty position; from there, to the last pair.
+    pub fn move_prev(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.move_prev(self.tree)
+    }
+
+    /// The key of the current pair, `None` on the empty position.
+    pub fn key(&self) -> Option<K> {
+        self.at.key()
+    }
+
+    /// The value of the current pair, which may be edited through its cell.
+    pub fn value(&self) -> Option<Rc<RefCell<T>>> {
+        self.at.value()
+    }
+
+    /// The current pair.
+    pub fn current(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.current()
+    }
+}
+
+impl<'a, T, K: Ord + Copy> BTreeCursorMut<'a, T, K> {
+    /// Moves to the first pair whose key is greater than or equal to `key`, or to the
+    /// empty position if there is none, and returns the key found.
+    pub fn seek(&mut self, key: &K) -> Option<K> {
+        self.at.seek(self.tree, key)
+    }
+
+    /// Moves to the next pair and returns it, wrapping around as `BTreeCursor` does.
+    pub fn move_next(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.move_next(self.tree)
+    }
+
+    /// Moves to the previous pair and returns it, wrapping around as `BTreeCursor` does.
+    pub fn move_prev(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.move_prev(self.tree)
+    }
+
+    /// The key of the current pair, `None` on the empty position.
+    pub fn key(&self) -> Option<K> {
+        self.at.key()
+    }
+
+    /// The value of the current pair, which may be edited through its cell.
+    pub fn value(&self) -> Option<Rc<RefCell<T>>> {
+        self.at.value()
+    }
+
+    /// The current pair.
+    pub fn current(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.at.current()
+    }
+
+    /// Puts `value` into a new cell for the current pair and returns the old cell, as an
+    /// insert that replaces a value does.
+    pub fn replace_value(&mut self, value: T) -> Option<Rc<RefCell<T>>> {
+        let (br, pos) = self.at.0.as_ref()?;
+        let cell = Rc::new(RefCell::new(value));
+        if !br.borrow().frozen() {
+            return Some(mem::replace(&mut br.borrow_mut().vals[*pos], cell));
+        }
+        // A snapshot holds the leaf, so the tree copies it and the cursor moves over.
+        let key = self.at.key()?;
+        let old = self.tree.replace(key, cell);
+        self.at.seek(self.tree, &key);
+        Some(old)
+    }
+
+    /// Removes the current pair and moves on to the one after it.
+    ///
+    /// The pair goes straight out of the current leaf, in O(1), unless that would take
+    /// the leaf below its minimal occupancy, change the largest key the trunks above
+    /// know it by or change a leaf a snapshot holds. Then the tree rebalances as on
+    /// `delete` and the cursor seeks its place again, in O(log n).
+    pub fn remove_current(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        let (br, pos) = self.at.0.take()?;
+        let key = br.borrow().keys[pos];
+        let in_place = {
+            let b = br.borrow();
+            let is_root = matches!(self.tree, BTree::Br(root) if Rc::ptr_eq(root, &br));
+            !b.frozen() && pos + 1 < b.keys.len() && (is_root || b.min_size() < b.keys.len())
+        };
+        if in_place {
+            let value = {
+                let mut b = br.borrow_mut();
+                b.keys.remove(pos);
+                b.vals.remove(pos)
+            };
+            // The pair after it moved into its place.
+            self.at.0 = Some((br, pos));
+            return Some((key, value));
+        }
+        let value = br.borrow().vals[pos].clone();
+        // Merges may empty or refill the leaf the cursor stood on, so it starts over.
+        self.tree.delete(key);
+        self.at.seek(self.tree, &key);
+        Some((key, value))
+    }
+}
+
+impl<T, K: Ord + Copy> BTree<T, K> {
+    /// A read-only cursor standing on the first pair, or on the empty position if the
+    /// tree is empty.
+    pub fn cursor(&self) -> BTreeCursor<'_, T, K> {
+        BTreeCursor {
+            tree: self,
+            at: Position::first(self),
+        }
+    }
+
+    /// A cursor that can change the tree, standing where `cursor` starts.
+    pub fn cursor_mut(&mut self) -> BTreeCursorMut<'_, T, K> {
+        BTreeCursorMut {
+            at: Position::first(self),
+            tree: self,
+        }
+    }
+}
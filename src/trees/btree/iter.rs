@@ -0,0 +1,217 @@
+use std::{
+    cell::RefCell,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+};
+
+use super::{snapshot::Freeze, BTree, Branch};
+
+// A leaf together with a position in it. The front of an iterator stands on the next
+// pair to yield; the back stands right after it.
+pub(super) type At<T, K> = (Rc<RefCell<Branch<T, K>>>, usize);
+
+/// In-order iterator over a B-tree or a snapshot of it, from both ends.
+///
+/// It descends once to each end of its range and from there follows the links between
+/// the leaves, so a scan never climbs back through the trunks. Only where a snapshot
+/// left a link stale does it descend from the root to the next leaf instead.
+///
+/// It borrows the tree, which therefore cannot change under it; a snapshot can be
+/// iterated over while the tree goes on.
+pub struct Iter<'a, T, K: Ord> {
+    root: &'a BTree<T, K>,
+    // The snapshot iterated over, which stays frozen while the iterator lives; None
+    // for the tree itself.
+    view: Option<Rc<Freeze<T>>>,
+    front: Option<At<T, K>>,
+    back: Option<At<T, K>>,
+}
+
+impl<'a, T, K: Ord + Copy> Iter<'a, T, K> {
+    pub(super) fn new(
+        root: &'a BTree<T, K>,
+        view: Option<Rc<Freeze<T>>>,
+        lower: Bound<K>,
+        upper: Bound<K>,
+    ) -> Self {
+        let below_lower = |key: &K| !(lower, Bound::Unbounded).contains(key);
+        let within_upper = |key: &K| (Bound::Unbounded, upper).contains(key);
+        // The trunks bound each child from above, so both ends descend into the child
+        // that can hold the bound itself.
+        let front = root.seek(|key| below_lower(key), below_lower);
+        let back = match upper {
+            Bound::Unbounded => root.seek(|_| true, |_| true),
+            Bound::Included(u) | Bound::Excluded(u) => root.seek(|key| *key < u, within_upper),
+        };
+        Self {
+            root,
+            view,
+            front: Some(front),
+            back: Some(back),
+        }
+    }
+
+    fn epoch(&self) -> Option<u64> {
+        self.view.as_ref().map(|view| view.epoch)
+    }
+
+    // Moves the front off the end of its leaf, onto the next leaf holding a pair.
+    fn settle_front(&mut self) -> Option<K> {
+        loop {
+            let (br, pos) = self.front.as_ref()?;
+            let key = br.borrow().keys.get(*pos).copied();
+            if key.is_some() {
+                return key;
+            }
+            self.front = self.root.after(br, self.epoch());
+        }
+    }
+
+    // Moves the back off the start of its leaf, onto the end of the previous one.
+    fn settle_back(&mut self) -> Option<K> {
+        loop {
+            let (br, pos) = self.back.as_ref()?;
+            if 0 < *pos {
+                return Some(br.borrow().keys[*pos - 1]);
+            }
+            self.back = self.root.before(br, self.epoch());
+        }
+    }
+
+    // Both ends in place, or None once they have crossed.
+    fn settle(&mut self) -> Option<(K, K)> {
+        match (self.settle_front(), self.settle_back()) {
+            (Some(front), Some(back)) if front <= back => Some((front, back)),
+            _ => {
+                self.front = None;
+                self.back = None;
+                None
+            }
+        }
+    }
+}
+
+impl<T, K: Ord + Copy> Iterator for Iter<'_, T, K> {
+    type Item = (K, Rc<RefCell<T>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, last) = self.settle()?;
+        let (br, pos) = self.front.as_mut()?;
+        let value = br.borrow().vals[*pos].clone();
+        *pos += 1;
+        if key == last {
+            self.front = None;
+            self.back = None;
+        }
+        Some((key, value))
+    }
+}
+
+impl<T, K: Ord + Copy> DoubleEndedIterator for Iter<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (first, key) = self.settle()?;
+        let (br, pos) = self.back.as_mut()?;
+        *pos -= 1;
+        let value = br.borrow().vals[*pos].clone();
+        if key == first {
+            self.front = None;
+            self.back = None;
+        }
+        Some((key, value))
+    }
+}
+
+impl<T, K: Ord + Copy> BTree<T, K> {
+    pub(super) fn clone_handle(&self) -> Self {
+        match self {
+            BTree::Tr(tr) => BTree::Tr(tr.clone()),
+            BTree::Br(br) => BTree::Br(br.clone()),
+        }
+    }
+
+    // Descends past the separators for which `before` holds, and stops in the leaf
+    // after the keys for which `within` holds.
+    pub(super) fn seek<F, G>(&self, before: F, within: G) -> At<T, K>
+    where
+        F: Fn(&K) -> bool,
+        G: Fn(&K) -> bool,
+    {
+        let mut node = self.clone_handle();
+        loop {
+            let next = match node {
+                BTree::Br(br) => {
+                    let pos = br.borrow().keys.partition_point(&within);
+                    return (br, pos);
+                }
+                BTree::Tr(tr) => {
+                    let pos = tr.borrow().keys.partition_point(&before);
+                    let next = tr.borrow().vals[pos].clone_handle();
+                    next
+                }
+            };
+            node = next;
+        }
+    }
+
+    // The start of the leaf after `leaf`, in the snapshot of `epoch` or, for None, in
+    // the tree. The link to it counts unless a snapshot froze the leaves around it;
+    // then the next key is looked up from the root, and the leaf holding it.
+    pub(super) fn after(
+        &self,
+        leaf: &Rc<RefCell<Branch<T, K>>>,
+        epoch: Option<u64>,
+    ) -> Option<At<T, K>> {
+        let next = leaf.borrow().next.upgrade();
+        if let Some(next) = next.filter(|n| n.borrow().visible(epoch) && Branch::linked(leaf, n)) {
+            return Some((next, 0));
+        }
+        let last = *leaf.borrow().keys.last()?;
+        let (key, _) = self.successor(last)?;
+        Some(self.seek(|k| *k < key, |k| *k < key))
+    }
+
+    // The end of the leaf before `leaf`, found as `after` finds the next one.
+    pub(super) fn before(
+        &self,
+        leaf: &Rc<RefCell<Branch<T, K>>>,
+        epoch: Option<u64>,
+    ) -> Option<At<T, K>> {
+        let prev = leaf.borrow().prev.upgrade();
+        if let Some(prev) = prev.filter(|p| p.borrow().visible(epoch) && Branch::linked(p, leaf)) {
+            let len = prev.borrow().keys.len();
+            return Some((prev, len));
+        }
+        let first = *leaf.borrow().keys.first()?;
+        let (key, _) = self.predecessor(first)?;
+        Some(self.seek(|k| *k < key, |k| *k <= key))
+    }
+
+    /// Iterates over `(key, value)` pairs in ascending key order, or from the back in
+    /// descending order.
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter::new(self, None, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Iterates over the pairs whose keys fall into `range`, e.g. `tree.range(3..=7)`.
+    /// Finding both ends costs O(log n), after which each pair costs O(1).
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, T, K> {
+        Iter::new(
+            self,
+            None,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+
+    /// The number of pairs. Counts leaf by leaf, so it costs O(n / max).
+    pub fn len(&self) -> usize {
+        match self {
+            BTree::Br(br) => br.borrow().keys.len(),
+            BTree::Tr(tr) => tr.borrow().vals.iter().map(|val| val.len()).sum(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.first().is_none()
+    }
+}
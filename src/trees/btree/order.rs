@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::BTree;
+
+impl<T, K: Ord + Copy> BTree<T, K> {
+    pub fn first(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        match self {
+            BTree::Br(br) => {
+                let br = br.borrow();
+                br.keys.first().map(|key| (*key, br.vals[0].clone()))
+            }
+            BTree::Tr(tr) => tr.borrow().vals.iter().find_map(|val| val.first()),
+        }
+    }
+
+    pub fn last(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        match self {
+            BTree::Br(br) => {
+                let br = br.borrow();
+                br.keys
+                    .last()
+                    .map(|key| (*key, br.vals[br.vals.len() - 1].clone()))
+            }
+            BTree::Tr(tr) => tr.borrow().vals.iter().rev().find_map(|val| val.last()),
+        }
+    }
+
+    /// The pair with the largest key less than or equal to `key`.
+    pub fn floor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_below(key, true)
+    }
+
+    /// The pair with the smallest key greater than or equal to `key`.
+    pub fn ceiling(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_above(key, true)
+    }
+
+    /// The pair with the largest key strictly less than `key`.
+    pub fn predecessor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_below(key, false)
+    }
+
+    /// The pair with the smallest key strictly greater than `key`.
+    pub fn successor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.nearest_above(key, false)
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        let first = self.first()?;
+        self.delete(first.0);
+        Some(first)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, Rc<RefCell<T>>)> {
+        let last = self.last()?;
+        self.delete(last.0);
+        Some(last)
+    }
+
+    fn nearest_below(&self, key: K, inclusive: bool) -> Option<(K, Rc<RefCell<T>>)> {
+        match self {
+            BTree::Br(br) => {
+                let br = br.borrow();
+                let pos = match br.keys.binary_search(&key) {
+                    Ok(pos) if inclusive => pos + 1,
+                    Ok(pos) => pos,
+                    Err(pos) => pos,
+                };
+                if pos == 0 {
+                    None
+                } else {
+                    Some((br.keys[pos - 1], br.vals[pos - 1].clone()))
+                }
+            }
+            BTree::Tr(tr) => {
+                let tr = tr.borrow();
+                let pos = match tr.keys.binary_search(&key) {
+                    Ok(pos) => pos,
+                    Err(pos) => pos,
+                };
+                // Anything on the left of the branch holding `key` is below it.
+                tr.vals[pos]
+                    .nearest_below(key, inclusive)
+                    .or_else(|| tr.vals[..pos].iter().rev().find_map(|val| val.last()))
+            }
+        }
+    }
+
+    fn nearest_above(&self, key: K, inclusive: bool) -> Option<(K, Rc<RefCell<T>>)> {
+        match self {
+            BTree::Br(br) => {
+                let br = br.borrow();
+                let pos = match br.keys.binary_search(&key) {
+                    Ok(pos) if inclusive => pos,
+                    Ok(pos) => pos + 1,
+                    Err(pos) => pos,
+                };
+                br.keys.get(pos).map(|key| (*key, br.vals[pos].clone()))
+            }
+            BTree::Tr(tr) => {
+                let tr = tr.borrow();
+                let pos = match tr.keys.binary_search(&key) {
+                    Ok(pos) => pos,
+                    Err(pos) => pos,
+                };
+                // Anything on the right of the branch holding `key` is above it.
+                tr.vals[pos]
+                    .nearest_above(key, inclusive)
+                    .or_else(|| tr.vals[pos + 1..].iter().find_map(|val| val.first()))
+            }
+        }
+    }
+}
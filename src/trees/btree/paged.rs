@@ -0,0 +1,438 @@
+mod check;
+mod disk;
+mod edit;
+mod page;
+mod pool;
+mod wal;
+
+use std::{
+    cell::{Cell, RefCell},
+    ffi::OsString,
+    fs::OpenOptions,
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    vec,
+};
+
+use crate::{error::TreeError, trees::Duplicates};
+
+use self::{
+    disk::{Disk, Fuse},
+    page::{Header, Node, PageId, NONE},
+    pool::Pool,
+    wal::Log,
+};
+
+pub use self::page::{Fixed, PAGE_SIZE};
+
+// The log is folded into the file once it holds this many bytes.
+const LOG_LIMIT: u64 = 256 * PAGE_SIZE as u64;
+
+// The log lives next to the file, under its name with `.wal` appended.
+fn log_path(path: &Path) -> PathBuf {
+    let mut log = OsString::from(path);
+    log.push(".wal");
+    log.into()
+}
+
+fn open_disk(path: &Path, create: bool, truncate: bool, fuse: &Fuse) -> Result<Disk, TreeError> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(create)
+        .truncate(truncate)
+        .open(path)?;
+    Ok(Disk::new(file, fuse.clone()))
+}
+
+// Copies a value through its encoding, as `Fixed` values need not be `Clone`.
+fn copy<T: Fixed>(value: &T) -> T {
+    let mut buf = vec![0; T::SIZE];
+    value.encode(&mut buf);
+    T::decode(&buf)
+}
+
+/// A B-tree kept in a file, one node per page of `PAGE_SIZE` bytes.
+///
+/// The nodes are laid out as in `BTree`, with the leaves linked to their neighbours,
+/// but refer to each other by page number. A pool caches a bounded number of pages and
+/// writes them back as it evicts them, so the tree may outgrow the memory. Pages freed
+/// by merges are chained into a list and reused before the file grows.
+///
+/// Each insert and delete is made durable in a write-ahead log next to the file before
+/// it returns, and `open` replays the log, so a crash at any point, in the middle of a
+/// split or a merge too, loses at most the change it interrupted. The log is folded
+/// into the file on `flush`, when it grows long and when the tree is dropped.
+///
+/// Keys and values are copied in and out of the pages, hence `Fixed`.
+pub struct PagedBTree<T: Fixed, K: Fixed + Ord + Copy> {
+    pool: RefCell<Pool>,
+    log: Log,
+    header: Header,
+    fuse: Fuse,
+    // Set once a change failed halfway; the log then knows better than the pool.
+    broken: Cell<bool>,
+    dup: Duplicates<T>,
+    kinds: PhantomData<(T, K)>,
+}
+
+impl<T: Fixed, K: Fixed + Ord + Copy> PagedBTree<T, K> {
+    /// The largest order whose nodes fit into a page.
+    pub fn max_order() -> usize {
+        Node::<T, K>::max_order()
+    }
+
+    /// Creates an empty tree of order `max` in the file at `path`, replacing what was
+    /// there, and caches up to `cached` pages of it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is below 2 or above `max_order()`, or if `cached` is 0.
+    pub fn create<P: AsRef<Path>>(path: P, max: usize, cached: usize) -> Result<Self, TreeError> {
+        assert!(
+            2 <= max && max <= Self::max_order(),
+            "The order must be in [2, {}].",
+            Self::max_order()
+        );
+        let path = path.as_ref();
+        let fuse = Rc::new(Cell::new(None));
+        let file = open_disk(path, true, true, &fuse)?;
+        let log = Log::new(open_disk(&log_path(path), true, true, &fuse)?)?;
+        let header = Header {
+            max,
+            root: 1,
+            free: NONE,
+            pages: 2,
+            len: 0,
+        };
+        let mut tree = Self::assemble(Pool::new(file, cached), log, header, fuse);
+        tree.change(|tree| tree.store(1, &Self::leaf(Vec::new(), Vec::new(), NONE, NONE)))?;
+        tree.flush()?;
+        Ok(tree)
+    }
+
+    /// Opens a tree created by `create`, caching up to `cached` pages of it. Replays what
+    /// the log holds first, should the tree not have been dropped or flushed.
+    pub fn open<P: AsRef<Path>>(path: P, cached: usize) -> Result<Self, TreeError> {
+        let path = path.as_ref();
+        let fuse = Rc::new(Cell::new(None));
+        let mut file = open_disk(path, false, false, &fuse)?;
+        let mut log = Log::new(open_disk(&log_path(path), true, false, &fuse)?)?;
+        log.replay(&mut file)?;
+        let mut pool = Pool::new(file, cached);
+        let header = Header::decode::<T, K>(pool.read(0)?)?;
+        Ok(Self::assemble(pool, log, header, fuse))
+    }
+
+    fn assemble(pool: Pool, log: Log, header: Header, fuse: Fuse) -> Self {
+        Self {
+            pool: RefCell::new(pool),
+            log,
+            header,
+            fuse,
+            broken: Cell::new(false),
+            dup: Duplicates::Overwrite,
+            kinds: PhantomData,
+        }
+    }
+
+    /// Writes all changes to the file, syncs it and empties the log.
+    pub fn flush(&mut self) -> Result<(), TreeError> {
+        self.check_broken()?;
+        self.pool.borrow_mut().flush()?;
+        self.log.reset()?;
+        Ok(())
+    }
+
+    /// For testing recovery: lets `writes` more writes reach the files, the last of them
+    /// only halfway, and fails all writes after it, as if the machine stopped there.
+    pub fn crash_after(&mut self, writes: u64) {
+        self.fuse.set(Some(writes));
+    }
+
+    pub fn find(&self, key: K) -> Result<T, TreeError> {
+        let (_, node) = self.leaf_for(key)?;
+        match node {
+            Node::Leaf { keys, mut vals, .. } => match keys.binary_search(&key) {
+                Ok(pos) => Ok(vals.swap_remove(pos)),
+                Err(_) => Err(TreeError::KeyNotFound),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Sets how inserts treat a key that is already in the tree. A tree overwrites the
+    /// value until told otherwise; the policy is not stored in the file.
+    pub fn with_duplicates(mut self, dup: Duplicates<T>) -> Self {
+        self.dup = dup;
+        self
+    }
+
+    /// Inserts `value` and returns the one it replaced, if any. Under `Merge` it returns
+    /// the merged value instead.
+    pub fn insert(&mut self, key: K, value: T) -> Option<T> {
+        match self.try_insert(key, value) {
+            Ok(old) => old,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_insert(&mut self, key: K, value: T) -> Result<Option<T>, TreeError> {
+        let dup = self.dup;
+        self.change(|tree| {
+            let (id, node) = tree.leaf_for(key)?;
+            let Node::Leaf {
+                keys,
+                mut vals,
+                prev,
+                next,
+            } = node
+            else {
+                unreachable!()
+            };
+            match keys.binary_search(&key) {
+                Err(_) => tree.put(key, value).map(|_| None),
+                Ok(pos) => {
+                    let kept = match dup {
+                        Duplicates::Reject => return Err(TreeError::DuplicateKey),
+                        Duplicates::Overwrite => mem::replace(&mut vals[pos], value),
+                        Duplicates::Merge(merge) => {
+                            merge(&mut vals[pos], value);
+                            copy(&vals[pos])
+                        }
+                    };
+                    tree.store(id, &Self::leaf(keys, vals, prev, next))?;
+                    Ok(Some(kept))
+                }
+            }
+        })
+    }
+
+    pub fn delete(&mut self, key: K) -> bool {
+        match self.try_delete(key) {
+            Ok(()) => true,
+            Err(TreeError::KeyNotFound) => false,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_delete(&mut self, key: K) -> Result<(), TreeError> {
+        self.change(|tree| tree.remove(key))
+    }
+
+    // Makes the change and commits it with the header in one record of the log. A change
+    // failing after it wrote pages leaves the tree broken until it is opened again.
+    fn change<R, F>(&mut self, change: F) -> Result<R, TreeError>
+    where
+        F: FnOnce(&mut Self) -> Result<R, TreeError>,
+    {
+        self.check_broken()?;
+        let result = change(self).and_then(|r| {
+            let header = self.header;
+            let mut pool = self.pool.borrow_mut();
+            pool.write(0, |buf| header.encode::<T, K>(buf))?;
+            pool.commit(&mut self.log)?;
+            Ok(r)
+        });
+        if self.pool.borrow().is_pending() {
+            self.broken.set(true);
+        }
+        let r = result?;
+        if LOG_LIMIT <= self.log.len() {
+            self.flush()?;
+        }
+        Ok(r)
+    }
+
+    fn check_broken(&self) -> Result<(), TreeError> {
+        match self.broken.get() {
+            true => Err(TreeError::Io(
+                "a change failed halfway; open the tree again to recover".to_string(),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn put(&mut self, key: K, value: T) -> Result<(), TreeError> {
+        let root = self.header.root;
+        if let Some(split) = self.insert_at(root, key, value)? {
+            let id = self.alloc()?;
+            let trunk = Node::Trunk {
+                keys: vec![split.lower],
+                children: vec![root, split.right],
+                upbd: split.upper,
+            };
+            self.store(id, &trunk)?;
+            self.header.root = id;
+        }
+        self.header.len += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: K) -> Result<(), TreeError> {
+        let root = self.header.root;
+        self.delete_at(root, key)?;
+        if let Node::Trunk { keys, children, .. } = self.load(root)? {
+            if keys.is_empty() {
+                self.header.root = children[0];
+                self.release(root)?;
+            }
+        }
+        self.header.len -= 1;
+        Ok(())
+    }
+
+    /// The number of pairs.
+    pub fn len(&self) -> usize {
+        self.header.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// The number of pages in the file, the header and the free pages included.
+    pub fn pages(&self) -> u64 {
+        self.header.pages
+    }
+
+    /// Iterates over `(key, value)` pairs in ascending key order, reading leaf by leaf.
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        let mut id = self.header.root;
+        let first = loop {
+            match self.load(id) {
+                Ok(Node::Trunk { children, .. }) => id = children[0],
+                Ok(_) => break Ok(id),
+                Err(e) => break Err(e),
+            }
+        };
+        Iter {
+            tree: self,
+            keys: Vec::new().into_iter(),
+            vals: Vec::new().into_iter(),
+            next: first,
+        }
+    }
+
+    fn leaf(keys: Vec<K>, vals: Vec<T>, prev: PageId, next: PageId) -> Node<T, K> {
+        Node::Leaf {
+            keys,
+            vals,
+            prev,
+            next,
+        }
+    }
+
+    fn load(&self, id: PageId) -> Result<Node<T, K>, TreeError> {
+        self.check_broken()?;
+        Node::decode(id, self.pool.borrow_mut().read(id)?)
+    }
+
+    fn store(&self, id: PageId, node: &Node<T, K>) -> Result<(), TreeError> {
+        self.pool.borrow_mut().write(id, |buf| node.encode(buf))?;
+        Ok(())
+    }
+
+    // Takes a page off the free list, or adds one to the end of the file.
+    fn alloc(&mut self) -> Result<PageId, TreeError> {
+        if self.header.free == NONE {
+            self.header.pages += 1;
+            return Ok(self.header.pages - 1);
+        }
+        let id = self.header.free;
+        match self.load(id)? {
+            Node::Free { next } => self.header.free = next,
+            _ => {
+                return Err(TreeError::CorruptStructure(format!(
+                    "page {} is on the free list but in use",
+                    id
+                )))
+            }
+        }
+        Ok(id)
+    }
+
+    // Puts the page on the free list.
+    fn release(&mut self, id: PageId) -> Result<(), TreeError> {
+        self.store(
+            id,
+            &Node::Free {
+                next: self.header.free,
+            },
+        )?;
+        self.header.free = id;
+        Ok(())
+    }
+
+    // The leaf that holds key if anything does.
+    fn leaf_for(&self, key: K) -> Result<(PageId, Node<T, K>), TreeError> {
+        let mut id = self.header.root;
+        loop {
+            match self.load(id)? {
+                Node::Trunk { keys, children, .. } => {
+                    id = children[keys.binary_search(&key).unwrap_or_else(|pos| pos)];
+                }
+                Node::Free { .. } => {
+                    return Err(TreeError::CorruptStructure(format!(
+                        "page {} is free but in the tree",
+                        id
+                    )))
+                }
+                leaf => return Ok((id, leaf)),
+            }
+        }
+    }
+}
+
+impl<T: Fixed, K: Fixed + Ord + Copy> Drop for PagedBTree<T, K> {
+    fn drop(&mut self) {
+        // Errors cannot be reported from here; call flush to see them. The log keeps
+        // the changes either way.
+        let _ = self.flush();
+    }
+}
+
+/// Iterator over the pairs of a `PagedBTree`, from `PagedBTree::iter`.
+///
+/// Yields an error, and then nothing, if a page cannot be read.
+pub struct Iter<'a, T: Fixed, K: Fixed + Ord + Copy> {
+    tree: &'a PagedBTree<T, K>,
+    keys: vec::IntoIter<K>,
+    vals: vec::IntoIter<T>,
+    next: Result<PageId, TreeError>,
+}
+
+impl<T: Fixed, K: Fixed + Ord + Copy> Iterator for Iter<'_, T, K> {
+    type Item = Result<(K, T), TreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(key) = self.keys.next() {
+                return self.vals.next().map(|val| Ok((key, val)));
+            }
+            let id = match mem::replace(&mut self.next, Ok(NONE)) {
+                Ok(NONE) => return None,
+                Ok(id) => id,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.tree.load(id) {
+                Ok(Node::Leaf {
+                    keys, vals, next, ..
+                }) => {
+                    self.keys = keys.into_iter();
+                    self.vals = vals.into_iter();
+                    self.next = Ok(next);
+                }
+                Ok(_) => {
+                    return Some(Err(TreeError::CorruptStructure(format!(
+                        "leaf linked to page {}, which is no leaf",
+                        id
+                    ))))
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Debug;
+
+use crate::error::TreeError;
+
+use super::{
+    edit::{leaf_min, trunk_min},
+    page::{Fixed, Node, PageId, NONE},
+    PagedBTree,
+};
+
+fn corrupt(report: String) -> TreeError {
+    TreeError::CorruptStructure(report)
+}
+
+// What the walk has seen so far.
+struct Walk {
+    // Whether each page has turned up, in the tree or on the free list.
+    seen: Vec<bool>,
+    leaf_depth: Option<usize>,
+    last_leaf: PageId,
+    len: u64,
+}
+
+impl Walk {
+    fn visit(&mut self, id: PageId) -> Result<(), TreeError> {
+        match self.seen.get_mut(id as usize) {
+            Some(seen) if !*seen => {
+                *seen = true;
+                Ok(())
+            }
+            Some(_) => Err(corrupt(format!("page {} is reached twice", id))),
+            None => Err(corrupt(format!("page {} lies beyond the file", id))),
+        }
+    }
+}
+
+impl<T: Fixed, K: Fixed + Ord + Copy + Debug> PagedBTree<T, K> {
+    /// Verifies what `BTree::check_invariants` does for the tree, that the leaves are
+    /// linked in key order, that the length is right and that every page is either in
+    /// the tree or on the free list, exactly once. Reports the first violation found.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        let mut walk = Walk {
+            seen: vec![false; self.header.pages as usize],
+            leaf_depth: None,
+            last_leaf: NONE,
+            len: 0,
+        };
+        walk.visit(0)?;
+        self.check_page(self.header.root, None, None, true, 0, &mut walk)?;
+        if let Node::Leaf { next, .. } = self.load(walk.last_leaf)? {
+            if next != NONE {
+                return Err(corrupt(format!("the last leaf links to page {}", next)));
+            }
+        }
+        if walk.len != self.header.len {
+            return Err(corrupt(format!(
+                "{} pairs counted as {}",
+                walk.len, self.header.len
+            )));
+        }
+        let mut free = self.header.free;
+        while free != NONE {
+            walk.visit(free)?;
+            free = match self.load(free)? {
+                Node::Free { next } => next,
+                _ => return Err(corrupt(format!("page {} is free but in use", free))),
+            };
+        }
+        match walk.seen.iter().position(|seen| !seen) {
+            Some(lost) => Err(corrupt(format!("page {} is neither used nor free", lost))),
+            None => Ok(()),
+        }
+    }
+
+    // Checks the subtree whose keys must lie in (lower, upper].
+    fn check_page(
+        &self,
+        id: PageId,
+        lower: Option<K>,
+        upper: Option<K>,
+        is_root: bool,
+        depth: usize,
+        walk: &mut Walk,
+    ) -> Result<(), TreeError> {
+        walk.visit(id)?;
+        let max = self.header.max;
+        let in_bounds = |key: K| lower.is_none_or(|l| l < key) && upper.is_none_or(|u| key <= u);
+        let keys = match self.load(id)? {
+            Node::Leaf { keys, prev, .. } => {
+                if max < keys.len() || (!is_root && keys.len() < leaf_min(max)) {
+                    return Err(corrupt(format!(
+                        "leaf {:?} holds {} keys, outside of [{}, {}]",
+                        keys,
+                        keys.len(),
+                        leaf_min(max),
+                        max
+                    )));
+                }
+                if let Some(d) = walk.leaf_depth.filter(|d| *d != depth) {
+                    return Err(corrupt(format!("leaves at depths {} and {}", d, depth)));
+                }
+                walk.leaf_depth = Some(depth);
+                let linked = match walk.last_leaf {
+                    NONE => prev == NONE,
+                    last => {
+                        prev == last
+                            && matches!(self.load(last)?, Node::Leaf { next, .. } if next == id)
+                    }
+                };
+                if !linked {
+                    return Err(corrupt(format!(
+                        "leaf {:?} is not linked to the leaf before it",
+                        keys
+                    )));
+                }
+                walk.last_leaf = id;
+                walk.len += keys.len() as u64;
+                keys
+            }
+            Node::Trunk {
+                keys,
+                children,
+                upbd,
+            } => {
+                let min = if is_root { 2 } else { trunk_min(max) };
+                if children.len() < min || max + 1 < children.len() {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has {} children, outside of [{}, {}]",
+                        keys,
+                        children.len(),
+                        min,
+                        max + 1
+                    )));
+                }
+                let last = *keys.last().unwrap();
+                if upbd <= last || upper.is_some_and(|u| u < upbd) {
+                    return Err(corrupt(format!(
+                        "trunk {:?} has upbd {:?} outside of ({:?}, {:?}]",
+                        keys, upbd, last, upper
+                    )));
+                }
+                for (i, child) in children.iter().enumerate() {
+                    let lower = if i == 0 { lower } else { Some(keys[i - 1]) };
+                    let upper = Some(keys.get(i).copied().unwrap_or(upbd));
+                    self.check_page(*child, lower, upper, false, depth + 1, walk)?;
+                }
+                keys
+            }
+            Node::Free { .. } => {
+                return Err(corrupt(format!("page {} is free but in the tree", id)))
+            }
+        };
+        if let Some(pair) = keys.windows(2).find(|pair| pair[1] <= pair[0]) {
+            return Err(corrupt(format!(
+                "keys {:?} and {:?} out of order in page {}",
+                pair[0], pair[1], id
+            )));
+        }
+        if let Some(key) = keys.iter().find(|key| !in_bounds(**key)) {
+            return Err(corrupt(format!(
+                "key {:?} outside of the bounds {:?} and {:?} of page {}",
+                key, lower, upper, id
+            )));
+        }
+        Ok(())
+    }
+}
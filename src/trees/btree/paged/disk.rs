@@ -0,0 +1,83 @@
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    rc::Rc,
+};
+
+/// The writes left before an injected crash, shared by the files of a tree. `None`
+/// unless a test asked for a crash.
+pub(super) type Fuse = Rc<Cell<Option<u64>>>;
+
+fn crashed() -> io::Error {
+    io::Error::other("injected crash")
+}
+
+/// A file whose writes may be cut off by the fuse: the last write allowed goes only
+/// halfway, as a torn write would, and nothing reaches the file after it.
+pub(super) struct Disk {
+    file: File,
+    fuse: Fuse,
+}
+
+impl Disk {
+    pub(super) fn new(file: File, fuse: Fuse) -> Self {
+        Self { file, fuse }
+    }
+
+    pub(super) fn len(&self) -> io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    pub(super) fn read_at(&mut self, at: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(at))?;
+        self.file.read_exact(buf)
+    }
+
+    pub(super) fn read_all(&mut self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub(super) fn write_at(&mut self, at: u64, data: &[u8]) -> io::Result<()> {
+        let data = match self.fuse.get() {
+            Some(0) => return Err(crashed()),
+            Some(1) => &data[..data.len() / 2],
+            _ => data,
+        };
+        self.file.seek(SeekFrom::Start(at))?;
+        self.file.write_all(data)?;
+        self.burn()
+    }
+
+    pub(super) fn truncate(&mut self) -> io::Result<()> {
+        if self.fuse.get() == Some(0) {
+            return Err(crashed());
+        }
+        self.file.set_len(0)?;
+        self.burn()
+    }
+
+    pub(super) fn sync(&mut self) -> io::Result<()> {
+        if self.fuse.get() == Some(0) {
+            return Err(crashed());
+        }
+        self.file.sync_data()
+    }
+
+    fn burn(&mut self) -> io::Result<()> {
+        match self.fuse.get() {
+            Some(1) => {
+                self.fuse.set(Some(0));
+                Err(crashed())
+            }
+            Some(n) => {
+                self.fuse.set(Some(n - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::error::TreeError;
+
+use super::{
+    page::{Fixed, Node, PageId, NONE},
+    PagedBTree,
+};
+
+// What a node hands up after splitting: the bound of the half that stayed in its page,
+// and the page of the other half with its bound.
+pub(super) struct Split<K> {
+    pub(super) lower: K,
+    pub(super) right: PageId,
+    pub(super) upper: K,
+}
+
+fn corrupt(report: String) -> TreeError {
+    TreeError::CorruptStructure(report)
+}
+
+// The occupancy limits of BTree: leaves keep half of max keys, trunks half of max + 1
+// children but never fewer than two.
+pub(super) fn leaf_min(max: usize) -> usize {
+    max / 2 + max % 2
+}
+
+pub(super) fn trunk_min(max: usize) -> usize {
+    leaf_min(max).max(2)
+}
+
+impl<T: Fixed, K: Fixed + Ord + Copy> PagedBTree<T, K> {
+    // Inserts below the page and splits it once it overflows.
+    pub(super) fn insert_at(
+        &mut self,
+        id: PageId,
+        key: K,
+        value: T,
+    ) -> Result<Option<Split<K>>, TreeError> {
+        let max = self.header.max;
+        match self.load(id)? {
+            Node::Leaf {
+                mut keys,
+                mut vals,
+                prev,
+                next,
+            } => {
+                let pos = match keys.binary_search(&key) {
+                    Ok(_) => return Err(TreeError::DuplicateKey),
+                    Err(pos) => pos,
+                };
+                keys.insert(pos, key);
+                vals.insert(pos, value);
+                if keys.len() <= max {
+                    self.store(id, &Self::leaf(keys, vals, prev, next))?;
+                    return Ok(None);
+                }
+                let uks = keys.split_off(max.div_ceil(2));
+                let uvs = vals.split_off(max.div_ceil(2));
+                let right = self.alloc()?;
+                if next != NONE {
+                    self.set_prev(next, right)?;
+                }
+                let split = Split {
+                    lower: *keys.last().unwrap(),
+                    right,
+                    upper: *uks.last().unwrap(),
+                };
+                self.store(right, &Self::leaf(uks, uvs, id, next))?;
+                self.store(id, &Self::leaf(keys, vals, prev, right))?;
+                Ok(Some(split))
+            }
+            Node::Trunk {
+                mut keys,
+                mut children,
+                upbd,
+            } => {
+                let pos = keys.binary_search(&key).unwrap_or_else(|pos| pos);
+                if let Some(split) = self.insert_at(children[pos], key, value)? {
+                    keys.insert(pos, split.lower);
+                    children.insert(pos + 1, split.right);
+                }
+                let upbd = upbd.max(key);
+                if keys.len() <= max {
+                    self.store(
+                        id,
+                        &Node::Trunk {
+                            keys,
+                            children,
+                            upbd,
+                        },
+                    )?;
+                    return Ok(None);
+                }
+                // The last key of the lower half becomes its upbd.
+                let ucs = children.split_off(children.len() / 2);
+                let uks = keys.split_off(children.len());
+                let lower = keys.pop().unwrap();
+                let right = self.alloc()?;
+                let upper = Node::Trunk {
+                    keys: uks,
+                    children: ucs,
+                    upbd,
+                };
+                self.store(right, &upper)?;
+                let halved = Node::Trunk {
+                    keys,
+                    children,
+                    upbd: lower,
+                };
+                self.store(id, &halved)?;
+                Ok(Some(Split {
+                    lower,
+                    right,
+                    upper: upbd,
+                }))
+            }
+            Node::Free { .. } => Err(corrupt(format!("page {} is free but in the tree", id))),
+        }
+    }
+
+    // Deletes below the page and tells whether it fell below its minimal occupancy.
+    // Children that do are merged with a sibling, or share its pairs if both are full.
+    pub(super) fn delete_at(&mut self, id: PageId, key: K) -> Result<bool, TreeError> {
+        let max = self.header.max;
+        let (mut keys, mut children, upbd) = match self.load(id)? {
+            Node::Leaf {
+                mut keys,
+                mut vals,
+                prev,
+                next,
+            } => {
+                let pos = keys
+                    .binary_search(&key)
+                    .map_err(|_| TreeError::KeyNotFound)?;
+                keys.remove(pos);
+                vals.remove(pos);
+                let underflow = keys.len() < leaf_min(max);
+                self.store(id, &Self::leaf(keys, vals, prev, next))?;
+                return Ok(underflow);
+            }
+            Node::Trunk {
+                keys,
+                children,
+                upbd,
+            } => (keys, children, upbd),
+            Node::Free { .. } => {
+                return Err(corrupt(format!("page {} is free but in the tree", id)))
+            }
+        };
+
+        let pos = keys.binary_search(&key).unwrap_or_else(|pos| pos);
+        if !self.delete_at(children[pos], key)? {
+            return Ok(false);
+        }
+        // We base our target index on the lower one.
+        let pos = if pos == children.len() - 1 {
+            pos - 1
+        } else {
+            pos
+        };
+        let (lower, upper) = (children[pos], children.remove(pos + 1));
+        let separator = keys.remove(pos);
+        match (self.load(lower)?, self.load(upper)?) {
+            (
+                Node::Leaf {
+                    keys: mut lks,
+                    vals: mut lvs,
+                    prev,
+                    ..
+                },
+                Node::Leaf {
+                    keys: mut uks,
+                    vals: mut uvs,
+                    next,
+                    ..
+                },
+            ) => {
+                lks.append(&mut uks);
+                lvs.append(&mut uvs);
+                if max < lks.len() {
+                    // Share the keys evenly; both halves keep at least leaf_min.
+                    let uks = lks.split_off(lks.len() / 2);
+                    let uvs = lvs.split_off(lvs.len() / 2);
+                    keys.insert(pos, *lks.last().unwrap());
+                    children.insert(pos + 1, upper);
+                    self.store(lower, &Self::leaf(lks, lvs, prev, upper))?;
+                    self.store(upper, &Self::leaf(uks, uvs, lower, next))?;
+                } else {
+                    self.store(lower, &Self::leaf(lks, lvs, prev, next))?;
+                    if next != NONE {
+                        self.set_prev(next, lower)?;
+                    }
+                    self.release(upper)?;
+                }
+            }
+            (
+                Node::Trunk {
+                    keys: mut lks,
+                    children: mut lcs,
+                    ..
+                },
+                Node::Trunk {
+                    keys: mut uks,
+                    children: mut ucs,
+                    upbd: uupbd,
+                },
+            ) => {
+                // The separator in between bounds the last child of the lower one.
+                lks.push(separator);
+                lks.append(&mut uks);
+                lcs.append(&mut ucs);
+                if max + 1 < lcs.len() {
+                    let ucs = lcs.split_off(lcs.len() / 2);
+                    let uks = lks.split_off(lcs.len());
+                    let lupbd = lks.pop().unwrap();
+                    keys.insert(pos, lupbd);
+                    children.insert(pos + 1, upper);
+                    let lower_trunk = Node::Trunk {
+                        keys: lks,
+                        children: lcs,
+                        upbd: lupbd,
+                    };
+                    self.store(lower, &lower_trunk)?;
+                    let upper_trunk = Node::Trunk {
+                        keys: uks,
+                        children: ucs,
+                        upbd: uupbd,
+                    };
+                    self.store(upper, &upper_trunk)?;
+                } else {
+                    let merged = Node::Trunk {
+                        keys: lks,
+                        children: lcs,
+                        upbd: uupbd,
+                    };
+                    self.store(lower, &merged)?;
+                    self.release(upper)?;
+                }
+            }
+            _ => return Err(corrupt("siblings of different kinds".to_string())),
+        }
+        // Without an upper half the merged node is bounded by what bounded the upper
+        // one, which is the separator following it, or the upbd of this trunk.
+        let underflow = children.len() < trunk_min(max);
+        self.store(
+            id,
+            &Node::Trunk {
+                keys,
+                children,
+                upbd,
+            },
+        )?;
+        Ok(underflow)
+    }
+
+    fn set_prev(&mut self, id: PageId, prev: PageId) -> Result<(), TreeError> {
+        match self.load(id)? {
+            Node::Leaf {
+                keys, vals, next, ..
+            } => self.store(id, &Self::leaf(keys, vals, prev, next)),
+            _ => Err(corrupt(format!(
+                "leaf linked to page {}, which is no leaf",
+                id
+            ))),
+        }
+    }
+}
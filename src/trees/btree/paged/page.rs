@@ -0,0 +1,263 @@
+use std::mem;
+
+use crate::error::TreeError;
+
+pub(super) type PageId = u64;
+
+/// The size of every page in the file of a `PagedBTree`.
+pub const PAGE_SIZE: usize = 4096;
+
+// Page 0 holds the header, so no node lives there and 0 can stand for no page.
+pub(super) const NONE: PageId = 0;
+
+const MAGIC: [u8; 8] = *b"BTREEPG1";
+
+const FREE: u8 = 0;
+const LEAF: u8 = 1;
+const TRUNK: u8 = 2;
+
+// The kind and the number of keys.
+const NODE_HEADER: usize = 1 + 2;
+
+/// A type stored in a fixed number of bytes, which keys and values of a `PagedBTree`
+/// have to be.
+pub trait Fixed: Sized {
+    const SIZE: usize;
+
+    /// Writes the value into the first `SIZE` bytes of `buf`.
+    fn encode(&self, buf: &mut [u8]);
+
+    /// Reads a value back from the first `SIZE` bytes of `buf`.
+    fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! fixed_int {
+    ($($t:ty),*) => {
+        $(
+            impl Fixed for $t {
+                const SIZE: usize = mem::size_of::<$t>();
+
+                fn encode(&self, buf: &mut [u8]) {
+                    buf[..Self::SIZE].copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(buf: &[u8]) -> Self {
+                    Self::from_le_bytes(buf[..Self::SIZE].try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+fixed_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl<const N: usize> Fixed for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf[..N].copy_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        buf[..N].try_into().unwrap()
+    }
+}
+
+impl<A: Fixed, B: Fixed> Fixed for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
+
+    fn encode(&self, buf: &mut [u8]) {
+        self.0.encode(buf);
+        self.1.encode(&mut buf[A::SIZE..]);
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        (A::decode(buf), B::decode(&buf[A::SIZE..]))
+    }
+}
+
+// Writes fixed-size values one after another.
+struct Out<'a> {
+    buf: &'a mut [u8],
+    at: usize,
+}
+
+impl Out<'_> {
+    fn put<X: Fixed>(&mut self, x: &X) {
+        x.encode(&mut self.buf[self.at..]);
+        self.at += X::SIZE;
+    }
+}
+
+// Reads them back in the same order.
+struct In<'a> {
+    buf: &'a [u8],
+    at: usize,
+}
+
+impl In<'_> {
+    fn take<X: Fixed>(&mut self) -> X {
+        let x = X::decode(&self.buf[self.at..]);
+        self.at += X::SIZE;
+        x
+    }
+}
+
+/// The first page of the file, telling how to read the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Header {
+    pub(super) max: usize,
+    pub(super) root: PageId,
+    // The first page of the list of free pages.
+    pub(super) free: PageId,
+    // The pages in the file, this one included.
+    pub(super) pages: u64,
+    pub(super) len: u64,
+}
+
+impl Header {
+    pub(super) fn encode<T: Fixed, K: Fixed>(&self, buf: &mut [u8]) {
+        let mut out = Out { buf, at: 0 };
+        out.put(&MAGIC);
+        out.put(&(PAGE_SIZE as u32));
+        out.put(&(K::SIZE as u32));
+        out.put(&(T::SIZE as u32));
+        out.put(&(self.max as u32));
+        for n in [self.root, self.free, self.pages, self.len] {
+            out.put(&n);
+        }
+    }
+
+    // Fails on files of another kind, page size or key and value sizes.
+    pub(super) fn decode<T: Fixed, K: Fixed>(buf: &[u8]) -> Result<Self, TreeError> {
+        let mut src = In { buf, at: 0 };
+        if src.take::<[u8; 8]>() != MAGIC {
+            return Err(TreeError::CorruptStructure(
+                "not the file of a paged B-tree".to_string(),
+            ));
+        }
+        let sizes = [PAGE_SIZE, K::SIZE, T::SIZE];
+        let found = [(); 3].map(|_| src.take::<u32>() as usize);
+        if found != sizes {
+            return Err(TreeError::CorruptStructure(format!(
+                "pages, keys and values of {:?} bytes instead of {:?}",
+                found, sizes
+            )));
+        }
+        Ok(Self {
+            max: src.take::<u32>() as usize,
+            root: src.take(),
+            free: src.take(),
+            pages: src.take(),
+            len: src.take(),
+        })
+    }
+}
+
+/// A page as it is worked on in memory.
+#[derive(Debug)]
+pub(super) enum Node<T, K> {
+    Leaf {
+        keys: Vec<K>,
+        vals: Vec<T>,
+        prev: PageId,
+        next: PageId,
+    },
+    Trunk {
+        keys: Vec<K>,
+        children: Vec<PageId>,
+        upbd: K,
+    },
+    Free {
+        next: PageId,
+    },
+}
+
+impl<T: Fixed, K: Fixed> Node<T, K> {
+    // The largest order whose leaves and trunks fit into a page.
+    pub(super) fn max_order() -> usize {
+        let leaf = (PAGE_SIZE - NODE_HEADER - 2 * PageId::SIZE) / (K::SIZE + T::SIZE);
+        let trunk = (PAGE_SIZE - NODE_HEADER - K::SIZE - PageId::SIZE) / (K::SIZE + PageId::SIZE);
+        leaf.min(trunk)
+    }
+
+    pub(super) fn encode(&self, buf: &mut [u8]) {
+        let mut out = Out { buf, at: 0 };
+        match self {
+            Node::Leaf {
+                keys,
+                vals,
+                prev,
+                next,
+            } => {
+                out.put(&LEAF);
+                out.put(&(keys.len() as u16));
+                out.put(prev);
+                out.put(next);
+                keys.iter().for_each(|key| out.put(key));
+                vals.iter().for_each(|val| out.put(val));
+            }
+            Node::Trunk {
+                keys,
+                children,
+                upbd,
+            } => {
+                out.put(&TRUNK);
+                out.put(&(keys.len() as u16));
+                out.put(upbd);
+                keys.iter().for_each(|key| out.put(key));
+                children.iter().for_each(|child| out.put(child));
+            }
+            Node::Free { next } => {
+                out.put(&FREE);
+                out.put(&0u16);
+                out.put(next);
+            }
+        }
+    }
+
+    pub(super) fn decode(id: PageId, buf: &[u8]) -> Result<Self, TreeError> {
+        let mut src = In { buf, at: 0 };
+        let kind: u8 = src.take();
+        let len = src.take::<u16>() as usize;
+        let size = match kind {
+            LEAF => NODE_HEADER + 2 * PageId::SIZE + len * (K::SIZE + T::SIZE),
+            TRUNK => NODE_HEADER + K::SIZE + len * K::SIZE + (len + 1) * PageId::SIZE,
+            _ => 0,
+        };
+        if buf.len() < size {
+            return Err(TreeError::CorruptStructure(format!(
+                "page {} claims {} keys, more than fit into it",
+                id, len
+            )));
+        }
+        match kind {
+            LEAF => {
+                let (prev, next) = (src.take(), src.take());
+                let keys = (0..len).map(|_| src.take()).collect();
+                let vals = (0..len).map(|_| src.take()).collect();
+                Ok(Node::Leaf {
+                    keys,
+                    vals,
+                    prev,
+                    next,
+                })
+            }
+            TRUNK => {
+                let upbd = src.take();
+                let keys = (0..len).map(|_| src.take()).collect();
+                let children = (0..=len).map(|_| src.take()).collect();
+                Ok(Node::Trunk {
+                    keys,
+                    children,
+                    upbd,
+                })
+            }
+            FREE => Ok(Node::Free { next: src.take() }),
+            _ => Err(TreeError::CorruptStructure(format!(
+                "page {} is of unknown kind {}",
+                id, kind
+            ))),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+};
+
+use super::{
+    disk::Disk,
+    page::{PageId, PAGE_SIZE},
+    wal::Log,
+};
+
+// A cached page, stamped with the tick it was last used at.
+struct Frame {
+    data: Box<[u8]>,
+    dirty: bool,
+    used: u64,
+}
+
+/// A cache of up to `capacity` pages of a file.
+///
+/// Pages are written back only when they are evicted, least recently used first, or
+/// flushed, so a page changed over and over costs a single write. The pages changed
+/// since the last commit stay in the cache until their record is in the log, which
+/// may take the pool over its capacity for the span of one change.
+pub(super) struct Pool {
+    disk: Disk,
+    frames: HashMap<PageId, Frame>,
+    // The cached pages by the tick they were last used at, least recent first.
+    recency: BTreeMap<u64, PageId>,
+    // The pages written since the last commit.
+    pending: BTreeSet<PageId>,
+    capacity: usize,
+    tick: u64,
+}
+
+impl Pool {
+    pub(super) fn new(disk: Disk, capacity: usize) -> Self {
+        assert!(0 < capacity, "The pool must hold at least one page.");
+        Self {
+            disk,
+            frames: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+            pending: BTreeSet::new(),
+            capacity,
+            tick: 0,
+        }
+    }
+
+    /// The page, read in from the file unless it is cached.
+    pub(super) fn read(&mut self, id: PageId) -> io::Result<&[u8]> {
+        if !self.frames.contains_key(&id) {
+            let mut data = vec![0; PAGE_SIZE].into_boxed_slice();
+            self.disk.read_at(id * PAGE_SIZE as u64, &mut data)?;
+            self.admit(id, data, false)?;
+        }
+        Ok(&self.touch(id).data)
+    }
+
+    /// Overwrites the whole page in the cache, leaving the file as it is for now.
+    pub(super) fn write<F: FnOnce(&mut [u8])>(&mut self, id: PageId, fill: F) -> io::Result<()> {
+        let mut data = match self.frames.remove(&id) {
+            Some(frame) => {
+                self.recency.remove(&frame.used);
+                frame.data
+            }
+            None => vec![0; PAGE_SIZE].into_boxed_slice(),
+        };
+        data.fill(0);
+        fill(&mut data);
+        self.pending.insert(id);
+        self.admit(id, data, true)
+    }
+
+    /// Whether pages were written since the last commit.
+    pub(super) fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Records the pages written since the last commit in the log, after which they may
+    /// be written back.
+    pub(super) fn commit(&mut self, log: &mut Log) -> io::Result<()> {
+        let frames = &self.frames;
+        log.append(self.pending.iter().map(|id| (*id, &frames[id].data[..])))?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Writes the changed pages back, in file order, and syncs the file.
+    pub(super) fn flush(&mut self) -> io::Result<()> {
+        debug_assert!(self.pending.is_empty(), "Flushing an uncommitted change.");
+        let mut dirty: Vec<PageId> = self
+            .frames
+            .iter()
+            .filter(|(_, frame)| frame.dirty)
+            .map(|(id, _)| *id)
+            .collect();
+        dirty.sort_unstable();
+        for id in dirty {
+            let frame = self.frames.get_mut(&id).unwrap();
+            self.disk.write_at(id * PAGE_SIZE as u64, &frame.data)?;
+            frame.dirty = false;
+        }
+        self.disk.sync()
+    }
+
+    fn touch(&mut self, id: PageId) -> &mut Frame {
+        self.tick += 1;
+        let frame = self.frames.get_mut(&id).unwrap();
+        self.recency.remove(&frame.used);
+        self.recency.insert(self.tick, id);
+        frame.used = self.tick;
+        frame
+    }
+
+    // Caches the page, evicting the least recently used committed ones while the pool
+    // is full.
+    fn admit(&mut self, id: PageId, data: Box<[u8]>, dirty: bool) -> io::Result<()> {
+        while self.capacity <= self.frames.len() {
+            let victim = self
+                .recency
+                .iter()
+                .find(|(_, id)| !self.pending.contains(id))
+                .map(|(used, id)| (*used, *id));
+            let Some((used, victim)) = victim else {
+                break;
+            };
+            let frame = &self.frames[&victim];
+            if frame.dirty {
+                self.disk.write_at(victim * PAGE_SIZE as u64, &frame.data)?;
+            }
+            self.frames.remove(&victim);
+            self.recency.remove(&used);
+        }
+        self.frames.insert(
+            id,
+            Frame {
+                data,
+                dirty,
+                used: 0,
+            },
+        );
+        self.touch(id);
+        Ok(())
+    }
+}
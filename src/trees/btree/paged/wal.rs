@@ -0,0 +1,102 @@
+use std::io;
+
+use super::{
+    disk::Disk,
+    page::{Fixed, PageId, PAGE_SIZE},
+};
+
+// The size of the length and the checksum in front of each record.
+const RECORD_HEADER: usize = 2 * u64::SIZE;
+
+// A page in a record, behind its number.
+const ENTRY: usize = u64::SIZE + PAGE_SIZE;
+
+// FNV-1a, which is enough to tell a record cut short by a crash from a whole one.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The write-ahead log of a paged tree.
+///
+/// Every insert and delete appends one record with the pages it changed, as they are
+/// after the change, header included, and syncs the log before it returns. Pages reach
+/// the tree's file only after their record, so after a crash the file may hold pages
+/// from any point since the last checkpoint, torn ones even, and replaying the whole
+/// records over it restores the tree as of the last change that returned. A record cut
+/// short fails its checksum and ends the replay.
+pub(super) struct Log {
+    disk: Disk,
+    len: u64,
+}
+
+impl Log {
+    pub(super) fn new(disk: Disk) -> io::Result<Self> {
+        let len = disk.len()?;
+        Ok(Self { disk, len })
+    }
+
+    /// The bytes in the log since the last checkpoint.
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Appends a record of the pages and syncs the log.
+    pub(super) fn append<'a, I>(&mut self, pages: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (PageId, &'a [u8])>,
+    {
+        let mut record = vec![0; RECORD_HEADER];
+        for (id, data) in pages {
+            record.extend(id.to_le_bytes());
+            record.extend(data);
+        }
+        let payload = (record.len() - RECORD_HEADER) as u64;
+        payload.encode(&mut record);
+        checksum(&record[RECORD_HEADER..]).encode(&mut record[u64::SIZE..]);
+        self.disk.write_at(self.len, &record)?;
+        self.disk.sync()?;
+        self.len += record.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the pages of every whole record into `file`, in order, syncs it and empties
+    /// the log. Returns the number of records replayed.
+    pub(super) fn replay(&mut self, file: &mut Disk) -> io::Result<usize> {
+        let log = self.disk.read_all()?;
+        let mut rest = &log[..];
+        let mut records = 0;
+        while RECORD_HEADER <= rest.len() {
+            let payload = u64::decode(rest) as usize;
+            let body = match rest[RECORD_HEADER..].get(..payload) {
+                Some(body)
+                    if payload.is_multiple_of(ENTRY)
+                        && checksum(body) == u64::decode(&rest[u64::SIZE..]) =>
+                {
+                    body
+                }
+                _ => break,
+            };
+            for page in body.chunks(ENTRY) {
+                let id = u64::decode(page);
+                file.write_at(id * PAGE_SIZE as u64, &page[u64::SIZE..])?;
+            }
+            rest = &rest[RECORD_HEADER + payload..];
+            records += 1;
+        }
+        if 0 < records {
+            file.sync()?;
+        }
+        self.reset()?;
+        Ok(records)
+    }
+
+    /// Empties the log once the tree's file holds everything it recorded.
+    pub(super) fn reset(&mut self) -> io::Result<()> {
+        self.disk.truncate()?;
+        self.disk.sync()?;
+        self.len = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    cell::RefCell,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+};
+
+use crate::error::TreeError;
+
+use super::{iter::Iter, trunk::refit, BTree, Branch, Shared, Trunk};
+
+/// A read-only view of a B-tree as it was when `BTree::snapshot` took it.
+///
+/// Taking one costs O(1): the snapshot shares the nodes with the tree. Each snapshot
+/// closes an epoch, and the tree copies a node of an epoch that a live snapshot still
+/// holds before it changes it, along with the nodes above it on the way down. So an
+/// insert or delete after a snapshot costs O(max log n) more until the touched paths
+/// are the tree's own again, while iterators and other handles cost nothing.
+///
+/// A snapshot freezes which keys there are and which cell each one maps to: an insert
+/// that replaces a value puts a new cell into a copy of the leaf. A value changed
+/// through its cell changes in the snapshot too, as with any other handle into the tree.
+pub struct Snapshot<T, K: Ord> {
+    root: BTree<T, K>,
+    freeze: Rc<Freeze<T>>,
+}
+
+// Keeps the nodes up to an epoch frozen while a snapshot, or an iterator over it, lives.
+pub(super) struct Freeze<T> {
+    shared: Rc<Shared<T>>,
+    pub(super) epoch: u64,
+}
+
+impl<T> Freeze<T> {
+    // Closes the current epoch of the tree.
+    fn new(shared: Rc<Shared<T>>) -> Self {
+        let epoch = shared.now();
+        shared.clock.set(epoch + 1);
+        shared.live.borrow_mut().insert(epoch);
+        Self { shared, epoch }
+    }
+}
+
+impl<T> Drop for Freeze<T> {
+    fn drop(&mut self) {
+        self.shared.live.borrow_mut().remove(&self.epoch);
+    }
+}
+
+impl<T, K: Ord + Copy> Snapshot<T, K> {
+    pub fn find(&self, key: K) -> Result<Rc<RefCell<T>>, TreeError> {
+        self.root.find(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.root.find(*key).is_ok()
+    }
+
+    pub fn first(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.root.first()
+    }
+
+    pub fn last(&self) -> Option<(K, Rc<RefCell<T>>)> {
+        self.root.last()
+    }
+
+    /// The pair with the largest key less than or equal to `key`.
+    pub fn floor(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.root.floor(key)
+    }
+
+    /// The pair with the smallest key greater than or equal to `key`.
+    pub fn ceiling(&self, key: K) -> Option<(K, Rc<RefCell<T>>)> {
+        self.root.ceiling(key)
+    }
+
+    /// The number of pairs. Counts leaf by leaf, so it costs O(n / max).
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_empty()
+    }
+
+    /// Iterates over `(key, value)` pairs in ascending key order, or from the back in
+    /// descending order.
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter::new(
+            &self.root,
+            Some(self.freeze.clone()),
+            Bound::Unbounded,
+            Bound::Unbounded,
+        )
+    }
+
+    /// Iterates over the pairs whose keys fall into `range`, e.g. `snapshot.range(3..=7)`.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, T, K> {
+        Iter::new(
+            &self.root,
+            Some(self.freeze.clone()),
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+}
+
+impl<T, K: Ord + Copy> Clone for Snapshot<T, K> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone_handle(),
+            freeze: self.freeze.clone(),
+        }
+    }
+}
+
+impl<T, K: Ord + Copy> BTree<T, K> {
+    /// A read-only view of the tree as it is now, which stays so while the tree changes.
+    ///
+    /// Values must be `Clone` so that the tree can merge duplicates into a copy of a value
+    /// the snapshot holds.
+    pub fn snapshot(&self) -> Snapshot<T, K>
+    where
+        T: Clone,
+    {
+        let _ = self.shared().copy.set(T::clone);
+        Snapshot {
+            root: self.clone_handle(),
+            freeze: Rc::new(Freeze::new(self.shared())),
+        }
+    }
+
+    // Copies the node if a snapshot holds it, so that it can be changed in place. Its
+    // children stay frozen until they are copied in turn, and a copied leaf takes the
+    // place of the original between its neighbours. A leaf's values are copied into new
+    // cells too, so that merges can change them in place.
+    pub(super) fn own(&mut self) {
+        match self {
+            BTree::Tr(tr) if tr.borrow().frozen() => {
+                let copy = {
+                    let tr = tr.borrow();
+                    Trunk::new(
+                        refit(tr.keys.clone(), tr.max_size()),
+                        tr.vals.iter().map(BTree::clone_handle).collect(),
+                        tr.upbd,
+                        tr.shared.clone(),
+                    )
+                };
+                *tr = Rc::new(RefCell::new(copy));
+            }
+            BTree::Br(br) if br.borrow().frozen() => {
+                let copy = {
+                    let br = br.borrow();
+                    let clone = br.shared.copy.get().expect("Only snapshots freeze leaves.");
+                    let cell = |v: &Rc<RefCell<T>>| Rc::new(RefCell::new(clone(&v.borrow())));
+                    let mut copy = Branch::new(
+                        refit(br.keys.clone(), br.max_size()),
+                        refit(br.vals.iter().map(cell).collect(), br.max_size()),
+                        br.shared.clone(),
+                    );
+                    copy.prev = br.prev.clone();
+                    copy.next = br.next.clone();
+                    copy
+                };
+                let copy = Rc::new(RefCell::new(copy));
+                Branch::supersede(br, &copy);
+                *br = copy;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,186 +1,217 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, mem, rc::Rc};
 
-use super::{BTree, Trunk};
+use crate::error::TreeError;
+
+use super::{BTree, Branch, Shared, Trunk};
+
+// Moves the items into a vector of the capacity that nodes of order max are built with.
+pub(super) fn refit<X>(items: Vec<X>, max: usize) -> Vec<X> {
+    let mut refitted = Vec::with_capacity(max + 1);
+    refitted.extend(items);
+    refitted
+}
 
 impl<T, K: Ord + Copy> Trunk<T, K> {
-    fn min_size(&self) -> usize {
-        self.max_size() / 2 + self.max_size() % 2
+    pub(super) fn new(
+        keys: Vec<K>,
+        vals: Vec<BTree<T, K>>,
+        upbd: K,
+        shared: Rc<Shared<T>>,
+    ) -> Self {
+        Self {
+            keys,
+            vals,
+            upbd,
+            epoch: shared.now(),
+            shared,
+        }
+    }
+
+    // Whether a snapshot holds the trunk, which must then stay as it is.
+    pub(super) fn frozen(&self) -> bool {
+        self.shared.frozen(self.epoch)
+    }
+
+    // A trunk needs two children to separate anything.
+    pub(super) fn min_size(&self) -> usize {
+        (self.max_size() / 2 + self.max_size() % 2).max(2)
     }
 
-    fn max_size(&self) -> usize {
-        self.vals.capacity() - 1
+    // The keys do not outgrow their capacity as the trunk splits right when they fill
+    // it, while the children may.
+    pub(super) fn max_size(&self) -> usize {
+        self.keys.capacity() - 1
     }
 
-    pub(super) fn insert(&mut self, mut key: K, value: T) -> Option<Self> {
+    pub(super) fn insert(&mut self, key: K, value: T) -> Result<Option<Self>, TreeError> {
         let pos = match self.keys.binary_search(&key) {
             Ok(pos) => pos,
             Err(pos) => pos,
         };
+        self.vals[pos].own();
         match self.vals[pos] {
             BTree::Br(ref br) => {
-                let branch = br.borrow_mut().insert(key, value);
+                let branch = br.borrow_mut().insert(key, value)?;
                 if let Some(branch) = branch {
+                    let branch = Rc::new(RefCell::new(branch));
+                    Branch::link_after(br, &branch);
                     self.keys
                         .insert(pos, *br.borrow().keys.iter().max().unwrap());
-                    self.vals
-                        .insert(pos + 1, BTree::Br(Rc::new(RefCell::new(branch))));
+                    self.vals.insert(pos + 1, BTree::Br(branch));
                     self.upbd = self.vals.last().unwrap().unwrap_br().borrow().upbd();
                 };
             }
             BTree::Tr(ref tr) => {
-                let trunk = tr.borrow_mut().insert(key, value);
+                let trunk = tr.borrow_mut().insert(key, value)?;
                 if let Some(trunk) = trunk {
-                    // Replace the existing key at pos.
-                    self.keys.insert(pos, trunk.upbd);
+                    // The lower half stays at pos and is bounded by its new upbd.
+                    self.keys.insert(pos, tr.borrow().upbd);
                     self.vals
                         .insert(pos + 1, BTree::Tr(Rc::new(RefCell::new(trunk))));
                     self.upbd = self.vals.last().unwrap().unwrap_tr().borrow().upbd;
                 };
             }
         };
+        if self.upbd < key {
+            self.upbd = key;
+        }
         if self.keys.len() == self.keys.capacity() {
             let mut keys = Vec::with_capacity(self.keys.capacity());
-            let mut vals = Vec::with_capacity(self.vals.capacity());
+            let mut vals = Vec::with_capacity(self.keys.capacity());
             let upbd = self.upbd;
             keys.extend(self.keys.split_off(self.vals.len() / 2));
             vals.extend(self.vals.split_off(self.vals.len() / 2));
-            // Remove the last key if it is tree as it's redundant.
-            self.upbd = self.keys.pop().unwrap();
+            self.vals.shrink_to(self.keys.capacity());
+            // The last separator is redundant next to the upbd.
+            self.keys.pop();
+            self.upbd = self.vals.last().unwrap().upbd();
 
-            Some(Self { keys, vals, upbd })
+            Ok(Some(Self::new(keys, vals, upbd, self.shared.clone())))
         } else {
-            None
+            Ok(None)
         }
     }
 
-    pub(super) fn delete(&mut self, key: K) -> bool {
-        let mut pos = match self.keys.binary_search(&key) {
+    pub(super) fn delete(&mut self, key: K) -> Result<(), TreeError> {
+        let pos = match self.keys.binary_search(&key) {
             Ok(pos) => pos,
             Err(pos) => pos,
         };
 
-        match self.vals[pos] {
+        self.vals[pos].own();
+        let underflow = match self.vals[pos] {
             BTree::Br(ref br) => {
-                if !br.borrow_mut().delete(key) {
-                    return false;
-                } else if br.borrow().min_size() <= br.borrow().vals.len() {
-                    return true;
-                }
+                br.borrow_mut().delete(key)?;
+                let br = br.borrow();
+                br.vals.len() < br.min_size()
             }
             BTree::Tr(ref tr) => {
-                if !tr.borrow_mut().delete(key) {
-                    return false;
-                } else if tr.borrow().min_size() <= tr.borrow().vals.len() {
-                    return true;
-                };
+                tr.borrow_mut().delete(key)?;
+                let tr = tr.borrow();
+                tr.vals.len() < tr.min_size()
             }
         };
+        if !underflow {
+            // The key may have been the largest one.
+            self.upbd = self.vals.last().unwrap().upbd();
+            return Ok(());
+        }
 
         // Here we merge the tree with another.
         // We base our target index on the lower one.
-        if pos == self.vals.len() - 1 {
-            pos = pos - 1;
+        let pos = if pos == self.vals.len() - 1 {
+            pos - 1
+        } else {
+            pos
         };
+        let max = self.max_size();
+        // Both siblings change, so neither may stay shared with a snapshot.
+        self.vals[pos].own();
+        self.vals[pos + 1].own();
         let lower = self.vals.remove(pos);
         let upper = self.vals.remove(pos);
+        let separator = self.keys.remove(pos);
 
-        match (lower, upper) {
+        let (lower, upper) = match (lower, upper) {
             (BTree::Br(lower), BTree::Br(upper)) => {
-                // remove the key at pos
-                self.keys.remove(pos);
-
-                let mut lks = Vec::with_capacity(2 * lower.borrow().keys.capacity());
-                lks.extend(lower.borrow_mut().keys.drain(..));
-                lks.extend(upper.borrow_mut().keys.drain(..));
-
-                let mut lvs = Vec::with_capacity(2 * lower.borrow().vals.capacity());
-                lvs.extend(lower.borrow_mut().vals.drain(..));
-                lvs.extend(upper.borrow_mut().vals.drain(..));
-
-                let upper = if self.max_size() < lvs.len() {
-                    let mut uks = Vec::with_capacity(upper.borrow().keys.capacity());
-                    let mut uvs = Vec::with_capacity(upper.borrow().vals.capacity());
-                    uks.extend(lks.split_off(lks.len() / 2));
-                    uvs.extend(lvs.split_off(lvs.len() / 2));
-                    let lower = super::Branch {
-                        keys: lks,
-                        vals: lvs,
-                    };
-                    let upper = super::Branch {
-                        keys: uks,
-                        vals: uvs,
-                    };
-                    self.keys.insert(pos, lower.upbd());
-                    // Inserts upper first.
-                    self.vals
-                        .insert(pos, BTree::Br(Rc::new(RefCell::new(upper))));
-                    // ... and then lower.
-                    self.vals
-                        .insert(pos, BTree::Br(Rc::new(RefCell::new(lower))));
+                // The leaves are refilled in place, which keeps them linked.
+                let mut keys = mem::take(&mut lower.borrow_mut().keys);
+                let mut vals = mem::take(&mut lower.borrow_mut().vals);
+                keys.append(&mut upper.borrow_mut().keys);
+                vals.append(&mut upper.borrow_mut().vals);
+
+                if max < vals.len() {
+                    // Share the keys evenly; both halves keep at least min_size.
+                    let uks = keys.split_off(keys.len() / 2);
+                    let uvs = vals.split_off(vals.len() / 2);
+                    lower.borrow_mut().keys = refit(keys, max);
+                    lower.borrow_mut().vals = refit(vals, max);
+                    upper.borrow_mut().keys = refit(uks, max);
+                    upper.borrow_mut().vals = refit(uvs, max);
+                    let lupbd = lower.borrow().upbd();
+                    (BTree::Br(lower), Some((lupbd, BTree::Br(upper))))
                 } else {
-                    let lower = super::Branch {
-                        keys: lks,
-                        vals: lvs,
-                    };
-                    self.vals
-                        .insert(pos, BTree::Br(Rc::new(RefCell::new(lower))));
-                };
+                    lower.borrow_mut().keys = refit(keys, max);
+                    lower.borrow_mut().vals = refit(vals, max);
+                    Branch::unlink(&lower, &upper);
+                    (BTree::Br(lower), None)
+                }
             }
             (BTree::Tr(lower), BTree::Tr(upper)) => {
-                let mut lks = Vec::with_capacity(2 * self.max_size());
-                let mut lvs = Vec::with_capacity(2 * self.max_size());
-
-                lks.extend(lower.borrow_mut().keys.drain(..));
-                lks.extend(upper.borrow_mut().keys.drain(..));
-
-                lvs.extend(lower.borrow_mut().vals.drain(..));
-                lvs.extend(upper.borrow_mut().vals.drain(..));
-
-                if self.max_size() < lvs.len() {
-                    let mut uks = Vec::with_capacity(upper.borrow().keys.capacity());
-                    let mut uvs = Vec::with_capacity(upper.borrow().vals.capacity());
-                    uks.extend(lks.split_off(lks.len() / 2));
-                    uvs.extend(lvs.split_off(lvs.len() / 2));
-
-                    lks.shrink_to(lower.borrow().keys.capacity());
-                    lvs.shrink_to(lower.borrow().vals.capacity());
-
-                    let lupbd = lvs.last().unwrap().unwrap_tr().borrow().upbd;
-                    let lower = Self {
-                        keys: lks,
-                        vals: lvs,
-                        upbd: lupbd,
-                    };
-                    let uupbd = uvs.last().unwrap().unwrap_tr().borrow().upbd;
-                    let upper = Self {
-                        keys: uks,
-                        vals: uvs,
-                        upbd: uupbd,
-                    };
-                    self.keys.insert(pos, lower.upbd);
-                    // Inserts upper first.
-                    self.vals
-                        .insert(pos, BTree::Tr(Rc::new(RefCell::new(upper))));
-                    // ...and then lower.
-                    self.vals
-                        .insert(pos, BTree::Tr(Rc::new(RefCell::new(lower))));
+                // The separator in between bounds the last child of the lower one.
+                let mut keys = mem::take(&mut lower.borrow_mut().keys);
+                let mut vals = mem::take(&mut lower.borrow_mut().vals);
+                keys.push(separator);
+                keys.append(&mut upper.borrow_mut().keys);
+                vals.append(&mut upper.borrow_mut().vals);
+                let upbd = upper.borrow().upbd;
+
+                if max + 1 < vals.len() {
+                    let uvs = vals.split_off(vals.len() / 2);
+                    let uks = keys.split_off(vals.len());
+                    // The last separator of the lower half gives way to its upbd.
+                    keys.pop();
+                    let lupbd = vals.last().unwrap().upbd();
+                    let lower = Self::new(
+                        refit(keys, max),
+                        refit(vals, max),
+                        lupbd,
+                        self.shared.clone(),
+                    );
+                    let upper =
+                        Self::new(refit(uks, max), refit(uvs, max), upbd, self.shared.clone());
+                    (
+                        BTree::Tr(Rc::new(RefCell::new(lower))),
+                        Some((lupbd, BTree::Tr(Rc::new(RefCell::new(upper))))),
+                    )
                 } else {
-                    lks.shrink_to(lower.borrow().keys.capacity());
-                    lvs.shrink_to(lower.borrow().vals.capacity());
-
-                    let lupbd = lvs.last().unwrap().unwrap_tr().borrow().upbd;
-                    let lower = Self {
-                        keys: lks,
-                        vals: lvs,
-                        upbd: lupbd,
-                    };
-                    self.vals
-                        .insert(pos, BTree::Tr(Rc::new(RefCell::new(lower))));
-                };
+                    let lower = Self::new(
+                        refit(keys, max),
+                        refit(vals, max),
+                        upbd,
+                        self.shared.clone(),
+                    );
+                    (BTree::Tr(Rc::new(RefCell::new(lower))), None)
+                }
+            }
+            _ => {
+                return Err(TreeError::CorruptStructure(
+                    "siblings of different kinds".to_string(),
+                ))
             }
-            _ => panic!("Something wrong with the algorithm."),
         };
 
-        return true;
+        // Without an upper half the merged tree is bounded by what bounded the upper one,
+        // which is the separator following it, or the upbd of self.
+        match upper {
+            Some((separator, upper)) => {
+                self.vals.insert(pos, upper);
+                self.vals.insert(pos, lower);
+                self.keys.insert(pos, separator);
+            }
+            None => self.vals.insert(pos, lower),
+        }
+        self.upbd = self.vals.last().unwrap().upbd();
+        Ok(())
     }
 }
@@ -0,0 +1,46 @@
+use std::{cell::RefCell, ops::RangeBounds, rc::Rc};
+
+use crate::error::TreeError;
+
+/// The map operations every map of the crate offers, the B-tree included, so that code
+/// can be written against any of them. Values are handed out as the shared cells the
+/// trees store them in.
+///
+/// The bare binary trees are not maps: they cannot be empty, so they could not give up
+/// their last pair. The maps wrapping them can.
+pub trait OrderedMap<K: Ord + Copy, V> {
+    /// Iterates over `(key, value)` pairs in ascending key order.
+    type Iter<'a>: Iterator<Item = (K, Rc<RefCell<V>>)>
+    where
+        Self: 'a;
+
+    /// Inserts `value`, settling an existing `key` by the map's duplicates policy, which
+    /// is `Duplicates::Overwrite` unless set otherwise. A value that gets replaced is
+    /// handed back in its cell; under `Merge` the key keeps its cell, which comes back
+    /// holding the merged value.
+    fn insert(&mut self, key: K, value: V) -> Option<Rc<RefCell<V>>>;
+
+    /// Removes `key` and returns its value, or fails with `TreeError::KeyNotFound`.
+    fn remove(&mut self, key: K) -> Result<Rc<RefCell<V>>, TreeError>;
+
+    fn get(&self, key: &K) -> Option<Rc<RefCell<V>>>;
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Iterates over the pairs whose keys fall into `range`.
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Iter<'_>;
+
+    fn first(&self) -> Option<(K, Rc<RefCell<V>>)>;
+
+    fn last(&self) -> Option<(K, Rc<RefCell<V>>)>;
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use std::{collections::BTreeMap, ops::Bound};
+
+use common::{check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::trees::{
+    bintree::{
+        aggregate::{Aggregate, Count, Max, Min, Sum},
+        avl::{AugmentedAvl, AvlMap, State},
+        BinTree,
+    },
+    Duplicates,
+};
+
+// The values come from all of i32, so the sum wraps instead of overflowing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct WrappingSum(i32);
+
+impl Aggregate<i32> for WrappingSum {
+    fn empty() -> Self {
+        Self(0)
+    }
+
+    fn of(value: &i32) -> Self {
+        Self(*value)
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        Self(self.0.wrapping_add(upper.0))
+    }
+}
+
+// The values of the lowest and the highest key, which depends on the order of combining.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Ends(Option<(i32, i32)>);
+
+impl Aggregate<i32> for Ends {
+    fn empty() -> Self {
+        Self(None)
+    }
+
+    fn of(value: &i32) -> Self {
+        Self(Some((*value, *value)))
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        match (self.0, upper.0) {
+            (Some((first, _)), Some((_, last))) => Self(Some((first, last))),
+            (a, b) => Self(a.or(b)),
+        }
+    }
+}
+
+// The number of values under all keys of a multimap.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Values(usize);
+
+impl Aggregate<Vec<u32>> for Values {
+    fn empty() -> Self {
+        Self(0)
+    }
+
+    fn of(values: &Vec<u32>) -> Self {
+        Self(values.len())
+    }
+
+    fn combine(&self, upper: &Self) -> Self {
+        Self(self.0 + upper.0)
+    }
+}
+
+type Summary = ((Min<i32>, Max<i32>), ((Count, WrappingSum), Ends));
+
+type Tree = BinTree<i32, u8, State, Summary>;
+
+fn expected(model: &BTreeMap<u8, i32>, bounds: (Bound<u8>, Bound<u8>)) -> Summary {
+    model
+        .range(bounds)
+        .fold(Summary::empty(), |acc, (_, v)| acc.combine(&Summary::of(v)))
+}
+
+fn bounds() -> impl Strategy<Value = (Bound<u8>, Bound<u8>)> {
+    let bound = prop_oneof![
+        (0u8..70).prop_map(Bound::Included),
+        (0u8..70).prop_map(Bound::Excluded),
+        Just(Bound::Unbounded),
+    ];
+    (bound.clone(), bound).prop_filter("start after end", |(a, b)| match (a, b) {
+        (Bound::Included(a), Bound::Included(b)) => a <= b,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => a < b,
+        _ => true,
+    })
+}
+
+fn run(
+    dup: Duplicates<i32>,
+    ops: Vec<Op>,
+    bounds: (Bound<u8>, Bound<u8>),
+) -> Result<(), TestCaseError> {
+    let mut map: AvlMap<u8, i32, Summary> = AvlMap::with_aggregate().with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let _ = map.remove(k);
+                model.remove(&k);
+            }
+            Op::Find(k) => {
+                // Edit the value through its cell, which the summaries only see on refresh.
+                if let Some(cell) = map.get(&k) {
+                    *cell.borrow_mut() ^= 1;
+                    *model.get_mut(&k).unwrap() ^= 1;
+                    prop_assert!(map.refresh(k));
+                }
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(
+            map.aggregate(..),
+            expected(&model, (Bound::Unbounded, Bound::Unbounded))
+        );
+        prop_assert_eq!(map.aggregate(bounds), expected(&model, bounds));
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn aggregates_match_a_fold(dup in policies(), ops in ops(64, 150), bounds in bounds()) {
+        run(dup, ops, bounds)?;
+    }
+
+    #[test]
+    fn aggregates_survive_split_and_append(
+        keys in prop::collection::btree_set(0u8..64, 1..64),
+        at in 0u8..64,
+    ) {
+        let pairs = keys.iter().map(|&k| (k, i32::from(k) * 3 - 50));
+        let tree = Tree::from_sorted_iter(pairs).unwrap().unwrap();
+        check(tree.check_aggregates())?;
+        let (left, _, right) = tree.split(at);
+        for half in [left.as_ref(), right.as_ref()].into_iter().flatten() {
+            check(half.check_invariants())?;
+            check(half.check_aggregates())?;
+        }
+        if let (Some(left), Some(right)) = (left, right) {
+            let tree = left.append(right);
+            check(tree.check_aggregates())?;
+        }
+    }
+}
+
+#[test]
+fn sums_a_window() {
+    let mut root: BinTree<u64, u32, State, Sum<u64>> = BinTree::new(0, 0);
+    for k in 1..1000 {
+        root = root.insert(k, u64::from(k)).unwrap();
+    }
+    assert_eq!(root.aggregate(..), Sum(999 * 1000 / 2));
+    assert_eq!(root.aggregate(10..20), Sum((10..20).sum()));
+    assert_eq!(root.aggregate(990..), Sum((990..1000).sum()));
+    assert_eq!(root.aggregate(2000..), Sum(0));
+    for k in (0..1000).step_by(2) {
+        root = root.delete(k).1.unwrap();
+    }
+    root.check_aggregates().unwrap();
+    assert_eq!(root.aggregate(..=10), Sum(1 + 3 + 5 + 7 + 9));
+}
+
+#[test]
+fn get_mut_keeps_the_summaries() {
+    let mut root: BinTree<u64, u32, State, Sum<u64>> = BinTree::new(0, 0);
+    for k in 1..100 {
+        root = root.insert(k, u64::from(k)).unwrap();
+    }
+    assert_eq!(root.get_mut(&40, |v| std::mem::replace(v, 1000)), Some(40));
+    assert_eq!(root.get_mut(&100, |v| *v = 0), None);
+    root.check_aggregates().unwrap();
+    assert_eq!(root.aggregate(..), Sum(99 * 100 / 2 - 40 + 1000));
+    assert_eq!(*root.get(&40).unwrap().borrow(), 1000);
+}
+
+#[test]
+fn appending_keeps_the_summaries() {
+    let mut map: AvlMap<u32, Vec<u32>, (Count, Values)> =
+        AvlMap::with_aggregate().with_duplicates(Duplicates::append());
+    for v in 0..300 {
+        map.insert(v % 30, vec![v]);
+    }
+    assert_eq!(map.aggregate(..), (Count(30), Values(300)));
+    assert_eq!(map.aggregate(..10), (Count(10), Values(100)));
+    let values: Vec<u32> = (7..300).step_by(30).collect();
+    assert_eq!(*map.get(&7).unwrap().borrow(), values);
+    map.into_root().unwrap().check_aggregates().unwrap();
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use std::{collections::BTreeMap, rc::Rc};
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{
+            avl::{Avl, AvlMap, State},
+            BinTree,
+        },
+        Duplicates,
+    },
+};
+
+type Tree = BinTree<i32, u8, State>;
+
+fn run(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut tree: Option<Tree> = None;
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let expected = common::insert(&mut model, Duplicates::Reject, k, v);
+                tree = Some(match tree.take() {
+                    None => Tree::new(k, v),
+                    Some(t) => {
+                        let (result, event) = t.try_insert(k, v);
+                        prop_assert_eq!(result, expected.map(|_| ()));
+                        event.into_option().unwrap()
+                    }
+                });
+            }
+            Op::Delete(k) => {
+                let expected = match model.remove(&k) {
+                    Some(_) => Ok(()),
+                    None => Err(TreeError::KeyNotFound),
+                };
+                if let Some(t) = tree.take() {
+                    let (result, event) = t.try_delete(k);
+                    prop_assert_eq!(result, expected);
+                    tree = event.into_option();
+                }
+            }
+            Op::Find(k) => {
+                let found = tree.as_ref().and_then(|t| t.get(&k)).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        match tree.as_ref() {
+            Some(t) => {
+                check(t.check_invariants())?;
+                prop_assert_eq!(t.len(), model.len());
+                assert_same(t.iter(), &model)?;
+            }
+            None => prop_assert!(model.is_empty()),
+        }
+    }
+    Ok(())
+}
+
+fn run_map(dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map = AvlMap::new().with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_first() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_first());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(ops in ops(64, 200)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(ops in ops(8, 100)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn avl_map_matches_btreemap(dup in policies(), ops in ops(16, 200)) {
+        run_map(dup, ops)?;
+    }
+}
+
+fn tree_of(keys: &[u8]) -> Tree {
+    let mut tree = Tree::new(keys[0], 0);
+    for &k in &keys[1..] {
+        tree = tree.insert(k, k as i32).unwrap();
+    }
+    tree
+}
+
+fn delete_and_check(tree: Tree, key: u8, rest: &[u8]) {
+    let (found, event) = tree.delete(key);
+    assert!(found);
+    let tree = event.into_option().unwrap();
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.keys().collect::<Vec<_>>(), rest);
+}
+
+#[test]
+fn rotates_over_a_balanced_sibling() {
+    // 2 with 1 on the left and 4, 3 and 5 on the right; dropping 1 leaves the root
+    // two deeper on the right, where 4 is balanced.
+    delete_and_check(tree_of(&[2, 1, 4, 3, 5]), 1, &[2, 3, 4, 5]);
+    delete_and_check(tree_of(&[4, 5, 2, 1, 3]), 5, &[1, 2, 3, 4]);
+}
+
+#[test]
+fn moves_up_the_left_branch_of_the_taken_leaf() {
+    // 4, the largest key left of 5, holds 3 on its left; 3 takes its place when 4
+    // replaces 5.
+    delete_and_check(
+        tree_of(&[5, 2, 8, 1, 4, 7, 9, 3]),
+        5,
+        &[1, 2, 3, 4, 7, 8, 9],
+    );
+}
+
+#[test]
+fn appends_into_the_stored_cell() {
+    let mut map = AvlMap::new().with_duplicates(Duplicates::append());
+    for v in 0..100u32 {
+        map.insert(v % 10, vec![v]);
+    }
+    let cell = map.get(&3).unwrap();
+    let merged = map.insert(3, vec![100]).unwrap();
+    assert!(Rc::ptr_eq(&cell, &merged));
+    let values: Vec<u32> = (3..100).step_by(10).chain([100]).collect();
+    assert_eq!(*cell.borrow(), values);
+}
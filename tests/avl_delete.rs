@@ -0,0 +1,57 @@
+use tree::trees::bintree::{
+    avl::{Avl, State},
+    BinTree,
+};
+
+type Tree = BinTree<i32, u8, State>;
+
+fn tree_of(keys: &[u8]) -> Tree {
+    let mut tree = Tree::new(keys[0], 0);
+    for &k in &keys[1..] {
+        tree = tree.insert(k, k as i32).unwrap();
+    }
+    tree
+}
+
+fn delete_and_find(tree: Tree, key: u8, rest: &[u8]) -> Tree {
+    let (found, event) = tree.delete(key);
+    assert!(found);
+    let tree = event.unwrap();
+    assert!(!tree.find(key).0);
+    for &k in rest {
+        assert_eq!(tree.find(k), (true, k));
+    }
+    tree
+}
+
+#[test]
+fn rotates_over_a_balanced_sibling() {
+    // 2 with 1 on the left and 4, 3 and 5 on the right; dropping 1 leaves the root
+    // two deeper on the right, where 4 is balanced.
+    delete_and_find(tree_of(&[2, 1, 4, 3, 5]), 1, &[2, 3, 4, 5]);
+    delete_and_find(tree_of(&[4, 5, 2, 1, 3]), 5, &[1, 2, 3, 4]);
+}
+
+#[test]
+fn moves_up_the_left_branch_of_the_taken_leaf() {
+    // 4, the largest key left of 5, holds 3 on its left; 3 takes its place when 4
+    // replaces 5.
+    delete_and_find(
+        tree_of(&[5, 2, 8, 1, 4, 7, 9, 3]),
+        5,
+        &[1, 2, 3, 4, 7, 8, 9],
+    );
+}
+
+#[test]
+fn deletes_down_to_one_key_in_scattered_order() {
+    let keys: Vec<u8> = (0..32u16).map(|i| (i * 13 % 32) as u8).collect();
+    for step in [1, 3, 5, 7] {
+        let mut tree = tree_of(&keys);
+        let mut rest: Vec<u8> = (0..32).collect();
+        while rest.len() > 1 {
+            let key = rest.remove((step * rest.len() / 8) % rest.len());
+            tree = delete_and_find(tree, key, &rest);
+        }
+    }
+}
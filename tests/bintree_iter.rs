@@ -0,0 +1,71 @@
+use std::ops::Bound;
+
+use tree::trees::bintree::{
+    avl::{Avl, State},
+    BinTree,
+};
+
+type Tree = BinTree<i32, u8, State>;
+
+fn tree_of(keys: impl IntoIterator<Item = u8>) -> Tree {
+    let mut keys = keys.into_iter();
+    let first = keys.next().unwrap();
+    let mut tree = Tree::new(first, first as i32);
+    for k in keys {
+        tree = tree.insert(k, k as i32).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn walks_in_key_order_from_both_ends() {
+    let tree = tree_of((0..50u16).map(|i| (i * 7 % 50) as u8));
+    assert_eq!(tree.keys().collect::<Vec<_>>(), (0..50).collect::<Vec<_>>());
+    assert_eq!(
+        tree.keys().rev().collect::<Vec<_>>(),
+        (0..50).rev().collect::<Vec<_>>()
+    );
+
+    // Both ends meet in the middle without handing out a pair twice.
+    let mut it = tree.keys();
+    let mut seen = Vec::new();
+    while let Some(k) = it.next() {
+        seen.push(k);
+        if let Some(k) = it.next_back() {
+            seen.push(k);
+        }
+    }
+    seen.sort();
+    assert_eq!(seen, (0..50).collect::<Vec<_>>());
+}
+
+#[test]
+fn keeps_to_the_range() {
+    let tree = tree_of((0..40).map(|i| i * 3 % 40));
+    assert_eq!(
+        tree.range(10..15).map(|(k, _)| k).collect::<Vec<_>>(),
+        [10, 11, 12, 13, 14]
+    );
+    assert_eq!(
+        tree.range(..=2).map(|(k, _)| k).collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        tree.range((Bound::Excluded(36), Bound::Unbounded))
+            .rev()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>(),
+        [39, 38, 37]
+    );
+    assert_eq!(tree.range(50..).count(), 0);
+}
+
+#[test]
+fn hands_out_the_stored_cells() {
+    let tree = tree_of([4, 2, 6, 1, 3, 5, 7]);
+    for value in tree.values() {
+        *value.borrow_mut() *= 10;
+    }
+    let values: Vec<i32> = tree.values().map(|v| *v.borrow()).collect();
+    assert_eq!(values, [10, 20, 30, 40, 50, 60, 70]);
+}
@@ -0,0 +1,440 @@
+mod common;
+
+use std::{collections::BTreeMap, iter, rc::Rc};
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::trees::{btree::BTree, Duplicates};
+
+/// One step of a cursor.
+#[derive(Debug, Clone)]
+enum Move {
+    Seek(u8),
+    Next,
+    Prev,
+    Replace(i32),
+    Remove,
+}
+
+fn moves() -> impl Strategy<Value = Vec<Move>> {
+    let step = prop_oneof![
+        1 => (0u8..200).prop_map(Move::Seek),
+        4 => Just(Move::Next),
+        3 => Just(Move::Prev),
+        1 => any::<i32>().prop_map(Move::Replace),
+        2 => Just(Move::Remove),
+    ];
+    prop::collection::vec(step, 0..300)
+}
+
+// Runs the moves on a cursor, with the model standing on a key or on nothing.
+fn steer(
+    tree: &mut BTree<i32, u8>,
+    model: &mut BTreeMap<u8, i32>,
+    moves: Vec<Move>,
+) -> Result<(), TestCaseError> {
+    let mut cursor = tree.cursor_mut();
+    let mut at = model.keys().next().copied();
+    prop_assert_eq!(cursor.key(), at);
+    for step in moves {
+        let expected = match step {
+            Move::Seek(k) => {
+                at = model.range(k..).next().map(|(k, _)| *k);
+                prop_assert_eq!(cursor.seek(&k), at);
+                continue;
+            }
+            Move::Next => {
+                at = match at {
+                    Some(k) => model.range(k..).nth(1),
+                    None => model.iter().next(),
+                }
+                .map(|(k, _)| *k);
+                cursor.move_next()
+            }
+            Move::Prev => {
+                at = match at {
+                    Some(k) => model.range(..k).next_back(),
+                    None => model.iter().next_back(),
+                }
+                .map(|(k, _)| *k);
+                cursor.move_prev()
+            }
+            Move::Replace(v) => {
+                let old = at.map(|k| model.insert(k, v).unwrap());
+                let replaced = cursor.replace_value(v).map(|old| *old.borrow());
+                prop_assert_eq!(replaced, old);
+                continue;
+            }
+            Move::Remove => {
+                let removed = at.map(|k| (k, model.remove(&k).unwrap()));
+                let found = cursor.remove_current();
+                prop_assert_eq!(found.map(|(k, v)| (k, *v.borrow())), removed);
+                at = removed.and_then(|(k, _)| model.range(k..).next().map(|(k, _)| *k));
+                cursor.current()
+            }
+        };
+        prop_assert_eq!(
+            expected.map(|(k, v)| (k, *v.borrow())),
+            at.map(|k| (k, model[&k]))
+        );
+    }
+    drop(cursor);
+    check(tree.check_invariants())?;
+    assert_same(tree.iter(), model)
+}
+
+// Takes a snapshot before each batch of ops, letting some go again, and checks in the
+// end that the ones kept still show the tree as it was, while the tree went on.
+fn freeze(
+    max: usize,
+    dup: Duplicates<i32>,
+    batches: Vec<Vec<Op>>,
+    (a, b): (u8, u8),
+) -> Result<(), TestCaseError> {
+    let mut tree = BTree::new(max).with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    let mut frozen = Vec::new();
+    for (i, batch) in batches.into_iter().enumerate() {
+        frozen.push((tree.snapshot(), model.clone()));
+        if i % 3 == 2 {
+            frozen.swap_remove(i / 3);
+        }
+        for op in batch {
+            match op {
+                // A replaced value goes into a copy of the leaf, out of the snapshots.
+                Op::Insert(k, v) => {
+                    let replaced = tree.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                    prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+                }
+                Op::Delete(k) => {
+                    prop_assert_eq!(tree.delete(k), model.remove(&k).is_some());
+                }
+                Op::Find(k) => {
+                    let found = tree.find(k).ok().map(|v| *v.borrow());
+                    prop_assert_eq!(found, model.get(&k).copied());
+                }
+            }
+            check(tree.check_invariants())?;
+        }
+    }
+    assert_same(tree.iter(), &model)?;
+    for (snapshot, model) in &frozen {
+        assert_same(snapshot.iter(), model)?;
+        prop_assert!(snapshot
+            .iter()
+            .rev()
+            .map(|(k, _)| k)
+            .eq(model.keys().rev().copied()));
+        prop_assert_eq!(snapshot.len(), model.len());
+        let found: Vec<u8> = snapshot.range(a..b).map(|(k, _)| k).collect();
+        prop_assert!(found.iter().eq(model.range(a..b.max(a)).map(|(k, _)| k)));
+        let first = snapshot.first().map(|(k, v)| (k, *v.borrow()));
+        prop_assert_eq!(first, model.first_key_value().map(|(k, v)| (*k, *v)));
+    }
+    Ok(())
+}
+
+fn run(
+    tree: BTree<i32, u8>,
+    mut model: BTreeMap<u8, i32>,
+    keys: u8,
+    dup: Duplicates<i32>,
+    ops: Vec<Op>,
+) -> Result<(), TestCaseError> {
+    let mut tree = tree.with_duplicates(dup);
+    check(tree.check_invariants())?;
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = tree.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                prop_assert_eq!(tree.delete(k), model.remove(&k).is_some());
+            }
+            Op::Find(k) => {
+                let found = tree.find(k).ok().map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(tree.check_invariants())?;
+        for k in 0..keys {
+            let found = tree.find(k).ok().map(|v| *v.borrow());
+            prop_assert_eq!(found, model.get(&k).copied());
+        }
+        // Walk the keys through first and successor.
+        let pairs = iter::successors(tree.first(), |(k, _)| tree.successor(*k));
+        assert_same(pairs, &model)?;
+        let last = tree.last().map(|(k, v)| (k, *v.borrow()));
+        prop_assert_eq!(last, model.last_key_value().map(|(k, v)| (*k, *v)));
+        // And backwards along the leaf links.
+        let keys: Vec<u8> = tree.iter().rev().map(|(k, _)| k).collect();
+        prop_assert!(keys.iter().eq(model.keys().rev()));
+    }
+    Ok(())
+}
+
+// Scans a..=b taking pairs from the front or the back as the steps say.
+fn scan(
+    tree: &BTree<i32, u8>,
+    model: &BTreeMap<u8, i32>,
+    a: u8,
+    b: u8,
+    steps: &[bool],
+) -> Result<(), TestCaseError> {
+    let mut it = tree.range(a..=b);
+    let mut expected = model.range(a..=b);
+    for &from_back in steps.iter().chain(iter::repeat_n(&true, model.len() + 1)) {
+        let (found, wanted) = match from_back {
+            true => (it.next_back(), expected.next_back()),
+            false => (it.next(), expected.next()),
+        };
+        prop_assert_eq!(
+            found.map(|(k, v)| (k, *v.borrow())),
+            wanted.map(|(k, v)| (*k, *v))
+        );
+    }
+    prop_assert!(it.next().is_none());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(max in 2usize..10, dup in policies(), ops in ops(64, 300)) {
+        run(BTree::new(max), BTreeMap::new(), 64, dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(max in 2usize..10, dup in policies(), ops in ops(16, 200)) {
+        run(BTree::new(max), BTreeMap::new(), 16, dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_from_sorted_iter(
+        max in 2usize..10,
+        fill in 0.05f64..=1.0,
+        model in prop::collection::btree_map(0u8..64, any::<i32>(), 0..64),
+        dup in policies(),
+        ops in ops(64, 100),
+    ) {
+        let tree = BTree::from_sorted_iter(model.iter().map(|(k, v)| (*k, *v)), max, fill);
+        run(tree.unwrap(), model, 64, dup, ops)?;
+    }
+
+    #[test]
+    fn scans_ranges_from_both_ends(
+        max in 2usize..10,
+        model in prop::collection::btree_map(0u8..200, any::<i32>(), 0..200),
+        deletes in prop::collection::vec(0u8..200, 0..100),
+        (a, b) in (0u8..200, 0u8..200),
+        steps in prop::collection::vec(any::<bool>(), 0..200),
+    ) {
+        let mut model = model;
+        let mut tree = BTree::new(max);
+        for (k, v) in model.iter() {
+            tree.insert(*k, *v);
+        }
+        // Deleting merges leaves, which has to keep them linked.
+        for k in deletes {
+            prop_assert_eq!(tree.delete(k), model.remove(&k).is_some());
+        }
+        check(tree.check_invariants())?;
+        let (a, b) = (a.min(b), a.max(b));
+        scan(&tree, &model, a, b, &steps)?;
+        let packed = BTree::from_sorted_iter(model.iter().map(|(k, v)| (*k, *v)), max, 1.0).unwrap();
+        check(packed.check_invariants())?;
+        scan(&packed, &model, a, b, &steps)?;
+    }
+
+    #[test]
+    fn cursor_matches_btreemap(
+        max in 2usize..10,
+        model in prop::collection::btree_map(0u8..200, any::<i32>(), 0..200),
+        moves in moves(),
+        frozen in any::<bool>(),
+    ) {
+        let mut model = model;
+        let mut tree = BTree::new(max);
+        for (k, v) in model.iter() {
+            tree.insert(*k, *v);
+        }
+        // A snapshot keeps the cursor from changing the leaves it holds in place.
+        let snapshot = frozen.then(|| (tree.snapshot(), model.clone()));
+        steer(&mut tree, &mut model, moves)?;
+        if let Some((snapshot, model)) = snapshot {
+            assert_same(snapshot.iter(), &model)?;
+        }
+    }
+
+    #[test]
+    fn read_only_cursor_walks_both_ways(
+        max in 2usize..10,
+        model in prop::collection::btree_map(0u8..200, any::<i32>(), 0..200),
+        key in 0u8..200,
+    ) {
+        let tree = BTree::from_sorted_iter(model.iter().map(|(k, v)| (*k, *v)), max, 0.7).unwrap();
+        let mut cursor = tree.cursor();
+        prop_assert_eq!(cursor.key(), model.keys().next().copied());
+        let found = cursor.seek(&key);
+        prop_assert_eq!(found, model.range(key..).next().map(|(k, _)| *k));
+        let mut after = Vec::new();
+        while let Some((k, _)) = found.and_then(|_| cursor.move_next()) {
+            after.push(k);
+        }
+        prop_assert!(after.iter().eq(model.range(key..).skip(1).map(|(k, _)| k)));
+        // From the empty position the cursor wraps around to the last pair.
+        let mut before = Vec::new();
+        while let Some((k, _)) = cursor.move_prev() {
+            before.push(k);
+        }
+        prop_assert!(before.iter().eq(model.keys().rev()));
+    }
+
+    #[test]
+    fn scans_a_snapshot_while_the_tree_changes(
+        max in 2usize..10,
+        model in prop::collection::btree_map(0u8..200, any::<i32>(), 1..200),
+        changes in prop::collection::vec((0u8..200, any::<bool>()), 0..200),
+        steps in prop::collection::vec(any::<bool>(), 0..200),
+    ) {
+        let mut tree = BTree::new(max);
+        for (k, v) in model.iter() {
+            tree.insert(*k, *v);
+        }
+        let snapshot = tree.snapshot();
+        let mut it = snapshot.iter();
+        let mut expected = model.iter();
+        let mut changes = changes.into_iter();
+        for &from_back in steps.iter().chain(iter::repeat_n(&true, model.len() + 1)) {
+            // Deletes shrink and merge the leaves the iterator stands in, unless the
+            // snapshot keeps them.
+            match changes.next() {
+                Some((k, true)) => {
+                    tree.insert(k, 0);
+                }
+                Some((k, false)) => {
+                    tree.delete(k);
+                }
+                None => {}
+            }
+            let (found, wanted) = match from_back {
+                true => (it.next_back(), expected.next_back()),
+                false => (it.next(), expected.next()),
+            };
+            prop_assert_eq!(
+                found.map(|(k, v)| (k, *v.borrow())),
+                wanted.map(|(k, v)| (*k, *v))
+            );
+        }
+        prop_assert!(it.next().is_none());
+        check(tree.check_invariants())?;
+    }
+
+    #[test]
+    fn snapshots_stay_as_taken(
+        max in 2usize..8,
+        dup in policies(),
+        batches in prop::collection::vec(ops(100, 40), 1..12),
+        bounds in (0u8..100, 0u8..100),
+    ) {
+        freeze(max, dup, batches, bounds)?;
+    }
+}
+
+#[test]
+fn keeps_keys_reachable_across_trunk_splits() {
+    // With order 2 trunks split every few inserts, three levels deep here; the halves
+    // need the right separator between them and bounds covering their largest keys.
+    let ascending: Vec<u8> = (0..100).collect();
+    let descending: Vec<u8> = (0..100).rev().collect();
+    let shuffled: Vec<u8> = (0..100u16).map(|k| (k * 37 % 101) as u8).collect();
+    let found_by_proptest = [vec![2, 0, 1, 3, 4], vec![4, 5, 6, 7, 0, 1]];
+    for keys in [ascending, descending, shuffled]
+        .into_iter()
+        .chain(found_by_proptest)
+    {
+        let mut tree = BTree::new(2);
+        for &k in &keys {
+            tree.insert(k, i32::from(k));
+            tree.check_invariants().unwrap();
+        }
+        for &k in &keys {
+            assert_eq!(tree.find(k).map(|v| *v.borrow()), Ok(i32::from(k)));
+        }
+    }
+}
+
+// Deletion orders that take trunks through borrowing from and merging with the
+// sibling on either side, until the root collapses into a leaf.
+fn deletion_orders(n: u8) -> [Vec<u8>; 4] {
+    let ascending: Vec<u8> = (0..n).collect();
+    let descending = ascending.iter().rev().copied().collect();
+    let outwards = (0..n).map(|i| {
+        if i % 2 == 0 {
+            n / 2 + i / 2
+        } else {
+            n / 2 - 1 - i / 2
+        }
+    });
+    let shuffled = (0..u16::from(n)).map(|k| (k * 37 % u16::from(n)) as u8);
+    [
+        ascending,
+        descending,
+        outwards.collect(),
+        shuffled.collect(),
+    ]
+}
+
+#[test]
+fn merges_trunks_back_into_a_leaf() {
+    for max in 2..=6 {
+        for order in deletion_orders(120) {
+            let mut tree = BTree::new(max);
+            for k in 0..120 {
+                tree.insert(k, i32::from(k));
+            }
+            for (i, &k) in order.iter().enumerate() {
+                assert!(tree.delete(k));
+                tree.check_invariants().unwrap();
+                assert_eq!(tree.len(), order.len() - i - 1);
+            }
+            assert!(matches!(tree, BTree::Br(_)));
+        }
+    }
+}
+
+#[test]
+fn trunks_keep_the_order_of_the_tree() {
+    // A trunk briefly holds max + 2 children before it splits, which must not leave
+    // it with room, and thus an order, beyond that of the tree.
+    for max in 2..=6 {
+        for order in deletion_orders(120) {
+            let mut tree = BTree::new(max);
+            for &k in &order {
+                tree.insert(k, i32::from(k));
+                tree.check_invariants().unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn appends_into_the_stored_cell() {
+    let mut tree = BTree::new(3).with_duplicates(Duplicates::append());
+    for k in 0..20u8 {
+        tree.insert(k, vec![u32::from(k)]);
+    }
+    let cell = tree.find(7).unwrap();
+    let merged = tree.insert(7, vec![70]).unwrap();
+    assert!(Rc::ptr_eq(&cell, &merged));
+    assert_eq!(*cell.borrow(), [7, 70]);
+
+    // The snapshot keeps the values it saw, while the tree appends to copies of them.
+    let snapshot = tree.snapshot();
+    tree.insert(7, vec![700]);
+    tree.insert(8, vec![80]);
+    assert_eq!(*snapshot.find(7).unwrap().borrow(), [7, 70]);
+    assert_eq!(*snapshot.find(8).unwrap().borrow(), [8]);
+    assert_eq!(*tree.find(7).unwrap().borrow(), [7, 70, 700]);
+    assert_eq!(*tree.find(8).unwrap().borrow(), [8, 80]);
+}
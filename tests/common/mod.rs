@@ -0,0 +1,74 @@
+// Each test crate compiles this module on its own and uses only part of it.
+#![allow(dead_code)]
+
+use std::{cell::RefCell, collections::BTreeMap, fmt::Debug, rc::Rc};
+
+use proptest::{prelude::*, test_runner::TestCaseError};
+use tree::{error::TreeError, trees::Duplicates};
+
+/// One step of a random operation sequence on a map.
+#[derive(Debug, Clone)]
+pub enum Op {
+    /// Insert, settling a key that is already there by the tree's policy.
+    Insert(u8, i32),
+    Delete(u8),
+    Find(u8),
+}
+
+/// Sequences of up to `len` operations on keys below `keys`, so that they collide often.
+pub fn ops(keys: u8, len: usize) -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        4 => (0..keys, any::<i32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        2 => (0..keys).prop_map(Op::Delete),
+        1 => (0..keys).prop_map(Op::Find),
+    ];
+    prop::collection::vec(op, 0..len)
+}
+
+/// The duplicates policies to run the trees with. `Merge` adds the values up.
+pub fn policies() -> impl Strategy<Value = Duplicates<i32>> {
+    prop_oneof![
+        Just(Duplicates::Reject),
+        Just(Duplicates::Overwrite),
+        Just(Duplicates::Merge(
+            |old: &mut i32, new| *old = old.wrapping_add(new)
+        )),
+    ]
+}
+
+/// Inserts into the model as a tree under `dup` does, and returns what the tree's
+/// `try_insert` should: the value it replaced, if any, the merged value under `Merge`,
+/// or the error.
+pub fn insert(
+    model: &mut BTreeMap<u8, i32>,
+    dup: Duplicates<i32>,
+    k: u8,
+    v: i32,
+) -> Result<Option<i32>, TreeError> {
+    let old = model.get(&k).copied();
+    match (old, dup) {
+        (None, _) | (Some(_), Duplicates::Overwrite) => Ok(model.insert(k, v)),
+        (Some(_), Duplicates::Reject) => Err(TreeError::DuplicateKey),
+        (Some(mut value), Duplicates::Merge(merge)) => {
+            merge(&mut value, v);
+            model.insert(k, value);
+            Ok(Some(value))
+        }
+    }
+}
+
+/// Compares the pairs of a tree, in iteration order, with the model.
+pub fn assert_same<I>(pairs: I, model: &BTreeMap<u8, i32>) -> Result<(), TestCaseError>
+where
+    I: Iterator<Item = (u8, Rc<RefCell<i32>>)>,
+{
+    let pairs: Vec<(u8, i32)> = pairs.map(|(k, v)| (k, *v.borrow())).collect();
+    let expected: Vec<(u8, i32)> = model.iter().map(|(k, v)| (*k, *v)).collect();
+    prop_assert_eq!(pairs, expected);
+    Ok(())
+}
+
+/// Turns a failed invariant check into a test failure carrying its report.
+pub fn check<E: Debug>(result: Result<(), E>) -> Result<(), TestCaseError> {
+    result.map_err(|e| TestCaseError::fail(format!("{:?}", e)))
+}
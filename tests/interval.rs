@@ -0,0 +1,103 @@
+mod common;
+
+use std::ops::Range;
+
+use common::check;
+use proptest::prelude::*;
+use tree::{error::TreeError, trees::bintree::interval::IntervalTree};
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(Range<u8>, i32),
+    Remove(Range<u8>),
+    Point(u8),
+    Window(Range<u8>),
+}
+
+fn interval() -> impl Strategy<Value = Range<u8>> {
+    (0u8..40, 1u8..12).prop_map(|(start, len)| start..start + len)
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        3 => (interval(), any::<i32>()).prop_map(|(r, v)| Op::Insert(r, v)),
+        1 => interval().prop_map(Op::Remove),
+        1 => (0u8..55).prop_map(Op::Point),
+        1 => (0u8..55, 0u8..55).prop_map(|(a, b)| Op::Window(a..b)),
+    ];
+    prop::collection::vec(op, 0..200)
+}
+
+// The intervals the model holds that pass the filter, by start and then insertion order.
+fn expected<F: Fn(&Range<u8>) -> bool>(model: &[(Range<u8>, i32)], f: F) -> Vec<(Range<u8>, i32)> {
+    let mut found: Vec<_> = model.iter().filter(|(r, _)| f(r)).cloned().collect();
+    found.sort_by_key(|(r, _)| r.start);
+    found
+}
+
+fn collect<I: Iterator<Item = (Range<u8>, std::rc::Rc<std::cell::RefCell<i32>>)>>(
+    it: I,
+) -> Vec<(Range<u8>, i32)> {
+    it.map(|(r, v)| (r, *v.borrow())).collect()
+}
+
+fn run(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut tree = IntervalTree::new();
+    let mut model: Vec<(Range<u8>, i32)> = Vec::new();
+    for op in ops {
+        match op {
+            Op::Insert(r, v) => {
+                tree.insert(r.clone(), v);
+                model.push((r, v));
+            }
+            Op::Remove(r) => {
+                let removed = tree.remove(r.clone()).map(|v| *v.borrow());
+                let expected = match model.iter().position(|(m, _)| *m == r) {
+                    Some(pos) => Ok(model.remove(pos).1),
+                    None => Err(TreeError::KeyNotFound),
+                };
+                prop_assert_eq!(removed, expected);
+            }
+            Op::Point(p) => {
+                let found = collect(tree.overlapping(p));
+                prop_assert_eq!(found, expected(&model, |r| r.contains(&p)));
+            }
+            Op::Window(w) => {
+                let found = collect(tree.overlapping(w.clone()));
+                let overlaps = |r: &Range<u8>| !w.is_empty() && r.start < w.end && w.start < r.end;
+                prop_assert_eq!(found, expected(&model, overlaps));
+            }
+        }
+        check(tree.check_invariants())?;
+        prop_assert_eq!(tree.len(), model.len());
+        prop_assert_eq!(tree.is_empty(), model.is_empty());
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_a_list_of_intervals(ops in ops()) {
+        run(ops)?;
+    }
+}
+
+#[test]
+fn finds_bookings_at_a_time() {
+    let tree: IntervalTree<u32, &str> = [(9..12, "a"), (10..11, "b"), (11..15, "c"), (13..14, "d")]
+        .into_iter()
+        .collect();
+    let at = |t| -> Vec<&str> { tree.overlapping(t).map(|(_, v)| *v.borrow()).collect() };
+    assert_eq!(at(10), ["a", "b"]);
+    assert_eq!(at(11), ["a", "c"]);
+    assert_eq!(at(15), Vec::<&str>::new());
+    let window: Vec<_> = tree.overlapping(12..14).map(|(r, _)| r).collect();
+    assert_eq!(window, [11..15, 13..14]);
+    assert_eq!(tree.overlapping(12..12).count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn rejects_empty_intervals() {
+    IntervalTree::new().insert(3..3, ());
+}
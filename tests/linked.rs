@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+
+use proptest::prelude::*;
+use tree::lists::linked::Linked;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Append(i32),
+    /// Deletes the element at this index, taken modulo the current length.
+    Delete(usize),
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        2 => any::<i32>().prop_map(Op::Append),
+        1 => any::<usize>().prop_map(Op::Delete),
+    ];
+    prop::collection::vec(op, 0..100)
+}
+
+proptest! {
+    #[test]
+    fn matches_vecdeque(first in any::<i32>(), ops in ops()) {
+        let head = Linked::new_head(first);
+        let mut model = VecDeque::from([first]);
+        for op in ops {
+            match op {
+                Op::Append(v) => {
+                    head.borrow_mut().append(v);
+                    model.push_back(v);
+                }
+                Op::Delete(n) if !model.is_empty() => {
+                    let n = n % model.len();
+                    Linked::delete_from_head(&head, n as u32);
+                    model.remove(n);
+                }
+                Op::Delete(_) => {}
+            }
+            prop_assert_eq!(Linked::values_from_head(&head), Vec::from(model.clone()));
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn delete_past_the_end() {
+    let head = Linked::new_head(1);
+    head.borrow_mut().append(2);
+    Linked::delete_from_head(&head, 2);
+}
+
+#[test]
+fn values_from_head_skip_the_head() {
+    let head = Linked::new_head(1);
+    head.borrow_mut().append(2);
+    assert_eq!(Linked::values_from_head(&head), [1, 2]);
+    Linked::delete_from_head(&head, 0);
+    Linked::delete_from_head(&head, 0);
+    assert!(Linked::values_from_head(&head).is_empty());
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::{collections::BTreeMap, ops::Bound};
+
+use common::{assert_same, ops, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{
+            avl::AvlMap,
+            plain::PlainMap,
+            rb::RbMap,
+            scapegoat::ScapegoatMap,
+            splay::SplayMap,
+            treap::{TreapMap, XorShift},
+        },
+        btree::BTree,
+        map::OrderedMap,
+    },
+};
+
+fn run<M: OrderedMap<u8, i32>>(
+    mut map: M,
+    mut model: BTreeMap<u8, i32>,
+    ops: Vec<Op>,
+    bounds: (Bound<u8>, Bound<u8>),
+) -> Result<(), TestCaseError> {
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.insert(k, v).map(|v| *v.borrow());
+                prop_assert_eq!(replaced, model.insert(k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+                prop_assert_eq!(map.contains_key(&k), model.contains_key(&k));
+            }
+        }
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+        let range = model.range(bounds).map(|(k, v)| (*k, *v)).collect();
+        assert_same(map.range(bounds), &range)?;
+        let first = map.first().map(|(k, v)| (k, *v.borrow()));
+        prop_assert_eq!(first, model.first_key_value().map(|(k, v)| (*k, *v)));
+        let last = map.last().map(|(k, v)| (k, *v.borrow()));
+        prop_assert_eq!(last, model.last_key_value().map(|(k, v)| (*k, *v)));
+    }
+    Ok(())
+}
+
+fn bounds() -> impl Strategy<Value = (Bound<u8>, Bound<u8>)> {
+    let bound = prop_oneof![
+        (0u8..70).prop_map(Bound::Included),
+        (0u8..70).prop_map(Bound::Excluded),
+        Just(Bound::Unbounded),
+    ];
+    (bound.clone(), bound).prop_filter("start after end", |(a, b)| match (a, b) {
+        (Bound::Included(a), Bound::Included(b)) => a <= b,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => a < b,
+        _ => true,
+    })
+}
+
+proptest! {
+    #[test]
+    fn plain_map_matches_btreemap(ops in ops(64, 150), bounds in bounds()) {
+        run(PlainMap::new(), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn avl_map_matches_btreemap(ops in ops(64, 150), bounds in bounds()) {
+        run(AvlMap::new(), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn rb_map_matches_btreemap(ops in ops(64, 150), bounds in bounds()) {
+        run(RbMap::new(), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn treap_map_matches_btreemap(seed in any::<u64>(), ops in ops(64, 150), bounds in bounds()) {
+        run(TreapMap::with_rng(XorShift::new(seed)), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn scapegoat_map_matches_btreemap(ops in ops(64, 150), bounds in bounds()) {
+        run(ScapegoatMap::new(), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn splay_map_matches_btreemap(ops in ops(64, 150), bounds in bounds()) {
+        run(SplayMap::new(), BTreeMap::new(), ops, bounds)?;
+    }
+
+    #[test]
+    fn btree_matches_btreemap(max in 2usize..10, ops in ops(64, 150), bounds in bounds()) {
+        run(BTree::new(max), BTreeMap::new(), ops, bounds)?;
+    }
+}
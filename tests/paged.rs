@@ -0,0 +1,242 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use common::{check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{btree::paged::PagedBTree, Duplicates},
+};
+
+// A file of its own for each tree, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "paged-{}-{}.btree",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        Self(env::temp_dir().join(name))
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        let mut log = self.0.clone().into_os_string();
+        log.push(".wal");
+        let _ = fs::remove_file(log);
+    }
+}
+
+fn assert_same(tree: &PagedBTree<i32, u8>, model: &BTreeMap<u8, i32>) -> Result<(), TestCaseError> {
+    let pairs: Result<Vec<(u8, i32)>, TreeError> = tree.iter().collect();
+    let expected: Vec<(u8, i32)> = model.iter().map(|(k, v)| (*k, *v)).collect();
+    prop_assert_eq!(pairs, Ok(expected));
+    prop_assert_eq!(tree.len(), model.len());
+    Ok(())
+}
+
+fn run(max: usize, cached: usize, dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let file = Scratch::new();
+    let mut tree = PagedBTree::create(&file.0, max, cached)
+        .unwrap()
+        .with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let expected = common::insert(&mut model, dup, k, v);
+                prop_assert_eq!(tree.try_insert(k, v), expected);
+            }
+            Op::Delete(k) => {
+                prop_assert_eq!(tree.delete(k), model.remove(&k).is_some());
+            }
+            Op::Find(k) => {
+                prop_assert_eq!(tree.find(k).ok(), model.get(&k).copied());
+            }
+        }
+        check(tree.check_invariants())?;
+    }
+    assert_same(&tree, &model)?;
+    // Everything has to come back from the file alone.
+    drop(tree);
+    let tree = PagedBTree::open(&file.0, cached).unwrap();
+    check(tree.check_invariants())?;
+    assert_same(&tree, &model)
+}
+
+// Runs the ops with a crash injected after so many writes, then opens the tree again
+// and expects it as it was before the change that crashed or after it. Tells whether
+// the crash came before the ops ran out.
+fn crash(
+    max: usize,
+    cached: usize,
+    dup: Duplicates<i32>,
+    writes: u64,
+    ops: &[Op],
+) -> Result<bool, TestCaseError> {
+    let file = Scratch::new();
+    let mut tree = PagedBTree::create(&file.0, max, cached)
+        .unwrap()
+        .with_duplicates(dup);
+    tree.crash_after(writes);
+    let mut model = BTreeMap::new();
+    let mut before = None;
+    for op in ops {
+        let last = model.clone();
+        let result = match *op {
+            Op::Insert(k, v) => {
+                let _ = common::insert(&mut model, dup, k, v);
+                tree.try_insert(k, v).map(|_| ())
+            }
+            Op::Delete(k) => {
+                model.remove(&k);
+                tree.try_delete(k)
+            }
+            Op::Find(k) => tree.find(k).map(|_| ()),
+        };
+        if let Err(TreeError::Io(_)) = result {
+            before = Some(last);
+            break;
+        }
+    }
+    // Whatever the tree still holds in memory is lost.
+    tree.crash_after(0);
+    drop(tree);
+    let tree = PagedBTree::open(&file.0, cached).unwrap();
+    check(tree.check_invariants())?;
+    let pairs: Vec<(u8, i32)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    let as_of = |model: &BTreeMap<u8, i32>| {
+        pairs.iter().eq(model
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>()
+            .iter())
+    };
+    prop_assert!(
+        as_of(&model) || before.as_ref().is_some_and(as_of),
+        "recovered {:?}",
+        pairs
+    );
+    Ok(before.is_some())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // Few cached pages make the pool evict on nearly every step.
+    #[test]
+    fn matches_btreemap(
+        max in 2usize..8,
+        cached in 1usize..6,
+        dup in policies(),
+        ops in ops(u8::MAX, 400),
+    ) {
+        run(max, cached, dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(max in 2usize..5, dup in policies(), ops in ops(24, 200)) {
+        run(max, 3, dup, ops)?;
+    }
+
+    #[test]
+    fn recovers_from_crashes(
+        max in 2usize..6,
+        cached in 1usize..5,
+        writes in 0u64..400,
+        dup in policies(),
+        ops in ops(64, 150),
+    ) {
+        crash(max, cached, dup, writes, &ops)?;
+    }
+}
+
+// Crashes at each write in turn while the tree grows to three levels and shrinks back,
+// which cuts splits and merges short at every step.
+#[test]
+fn recovers_from_a_crash_at_any_write() {
+    let keys = (0..40u8).map(|k| k.wrapping_mul(23) % 40);
+    let ops: Vec<Op> = keys
+        .clone()
+        .map(|k| Op::Insert(k, k.into()))
+        .chain(keys.map(Op::Delete))
+        .collect();
+    let mut writes = 0;
+    while crash(3, 2, Duplicates::Reject, writes, &ops).unwrap() {
+        writes += 1;
+    }
+    assert!(100 < writes);
+}
+
+#[test]
+fn reuses_freed_pages() {
+    let file = Scratch::new();
+    let mut tree = PagedBTree::<u64, u32>::create(&file.0, 4, 8).unwrap();
+    for k in 0..1000 {
+        tree.insert(k, u64::from(k) * 3);
+    }
+    let pages = tree.pages();
+    for k in 0..1000 {
+        assert!(tree.delete(k));
+    }
+    assert!(tree.is_empty());
+    tree.check_invariants().unwrap();
+    for k in (0..1000).rev() {
+        tree.insert(k, 0);
+    }
+    tree.check_invariants().unwrap();
+    assert!(tree.pages() <= pages);
+}
+
+#[test]
+fn overwrites_by_default() {
+    let file = Scratch::new();
+    let mut tree = PagedBTree::create(&file.0, 3, 2).unwrap();
+    assert_eq!(tree.insert(1u32, 10u32), None);
+    assert_eq!(tree.insert(1, 20), Some(10));
+    assert_eq!(tree.find(1), Ok(20));
+}
+
+#[test]
+fn outgrows_the_pool() {
+    let file = Scratch::new();
+    let max = PagedBTree::<[u8; 100], u64>::max_order();
+    let mut tree = PagedBTree::create(&file.0, max, 4).unwrap();
+    for k in 0..20_000u64 {
+        tree.insert(k.wrapping_mul(7919) % 20_000, [k as u8; 100]);
+    }
+    tree.flush().unwrap();
+    drop(tree);
+    let tree = PagedBTree::<[u8; 100], u64>::open(&file.0, 4).unwrap();
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.len(), 20_000);
+    assert_eq!(tree.find(7919), Ok([1; 100]));
+    assert!(fs::metadata(&file.0).unwrap().len() > 2_000_000);
+}
+
+#[test]
+fn rejects_other_files() {
+    let file = Scratch::new();
+    drop(PagedBTree::<u32, u32>::create(&file.0, 3, 2).unwrap());
+    assert!(matches!(
+        PagedBTree::<u64, u32>::open(&file.0, 2),
+        Err(TreeError::CorruptStructure(_))
+    ));
+    fs::write(&file.0, b"not a tree").unwrap();
+    assert!(matches!(
+        PagedBTree::<u32, u32>::open(&file.0, 2),
+        Err(TreeError::Io(_))
+    ));
+}
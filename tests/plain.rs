@@ -0,0 +1,177 @@
+mod common;
+
+use std::{collections::BTreeMap, mem};
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{
+            plain::{Plain, PlainMap},
+            BinTree,
+        },
+        Duplicates,
+    },
+};
+
+type Tree = BinTree<i32, u8, ()>;
+
+fn run(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut tree: Option<Tree> = None;
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let expected = common::insert(&mut model, Duplicates::Reject, k, v);
+                match tree.as_ref() {
+                    None => tree = Some(Tree::new(k, v)),
+                    Some(t) => prop_assert_eq!(t.try_insert(k, v), expected.map(|_| ())),
+                }
+            }
+            Op::Delete(k) => {
+                if let Some(t) = tree.as_ref() {
+                    // The plain tree cannot lose its root.
+                    let expected = match model.get(&k) {
+                        None => Err(TreeError::KeyNotFound),
+                        Some(_) if model.len() == 1 => Err(TreeError::LastNode),
+                        Some(_) => Ok(()),
+                    };
+                    if expected.is_ok() {
+                        model.remove(&k);
+                    }
+                    prop_assert_eq!(t.try_delete(k), expected);
+                }
+            }
+            Op::Find(k) => {
+                let found = tree.as_ref().is_some_and(|t| t.find(k).0);
+                prop_assert_eq!(found, model.contains_key(&k));
+            }
+        }
+        if let Some(t) = tree.as_ref() {
+            check(t.check_invariants())?;
+            prop_assert_eq!(t.len(), model.len());
+            assert_same(t.iter(), &model)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_map(dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map = PlainMap::new().with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_first() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_first());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(ops in ops(64, 200)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(ops in ops(8, 100)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn plain_map_matches_btreemap(dup in policies(), ops in ops(16, 200)) {
+        run_map(dup, ops)?;
+    }
+}
+
+// Keeps the first occurrence of each key, in their shuffled order.
+fn distinct(mut keys: Vec<u8>) -> Vec<u8> {
+    let mut seen = [false; 256];
+    keys.retain(|&k| !mem::replace(&mut seen[usize::from(k)], true));
+    keys
+}
+
+fn plain_tree(keys: &[u8]) -> Tree {
+    let tree = Tree::new(keys[0], i32::from(keys[0]));
+    for &k in &keys[1..] {
+        tree.insert(k, i32::from(k));
+    }
+    tree
+}
+
+proptest! {
+    #[test]
+    fn rebalance_keeps_the_pairs(keys in prop::collection::vec(any::<u8>(), 1..150).prop_map(distinct)) {
+        let tree = plain_tree(&keys);
+        let model: BTreeMap<u8, i32> = keys.iter().map(|&k| (k, i32::from(k))).collect();
+        tree.rebalance();
+        check(tree.check_invariants())?;
+        assert_same(tree.iter(), &model)?;
+    }
+
+    #[test]
+    fn convert_to_avl_keeps_the_pairs(keys in prop::collection::vec(any::<u8>(), 1..150).prop_map(distinct)) {
+        let model: BTreeMap<u8, i32> = keys.iter().map(|&k| (k, i32::from(k))).collect();
+        let avl = plain_tree(&keys).convert_to_avl();
+        check(avl.check_invariants())?;
+        assert_same(avl.iter(), &model)?;
+    }
+}
+
+#[test]
+fn rebalances_a_long_path() {
+    // Ascending inserts leave a path the rebalance must walk without recursing.
+    let tree = Tree::new(0, 0);
+    for k in 1..=u8::MAX {
+        tree.insert(k, i32::from(k));
+    }
+    tree.rebalance();
+    tree.check_invariants().unwrap();
+    let avl = tree.convert_to_avl();
+    avl.check_invariants().unwrap();
+    assert_eq!(avl.len(), 256);
+}
+
+#[test]
+fn deleting_the_root_keeps_the_handle() {
+    // 3, the floor of 5, is its direct left child and brings 1 along.
+    let tree = plain_tree(&[5, 3, 8, 1]);
+    tree.delete(5);
+    check(tree.check_invariants()).unwrap();
+    assert_eq!(tree.keys().collect::<Vec<_>>(), [1, 3, 8]);
+    tree.delete(3);
+    tree.delete(1);
+    assert_eq!(tree.keys().collect::<Vec<_>>(), [8]);
+    assert_eq!(tree.len(), 1);
+}
+
+#[test]
+fn a_lone_node_reports_last_node() {
+    let tree = Tree::new(1, 10);
+    assert_eq!(tree.try_delete(1), Err(TreeError::LastNode));
+    assert_eq!(tree.pop_first().map(|(k, _)| k), Err(TreeError::LastNode));
+    assert_eq!(tree.pop_last().map(|(k, _)| k), Err(TreeError::LastNode));
+    assert_eq!(tree.try_delete(2), Err(TreeError::KeyNotFound));
+    assert_eq!(tree.keys().collect::<Vec<_>>(), [1]);
+    assert_eq!(*tree.first().1.borrow(), 10);
+}
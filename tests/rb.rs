@@ -0,0 +1,167 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{
+            rb::{Color, Entry, RbMap, RedBlack},
+            BinTree,
+        },
+        Duplicates,
+    },
+};
+
+type Tree = BinTree<i32, u8, Color>;
+
+fn run(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut tree: Option<Tree> = None;
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let expected = common::insert(&mut model, Duplicates::Reject, k, v);
+                tree = Some(match tree.take() {
+                    None => Tree::new(k, v),
+                    Some(t) => {
+                        let (result, t) = t.try_insert(k, v);
+                        prop_assert_eq!(result, expected.map(|_| ()));
+                        t
+                    }
+                });
+            }
+            Op::Delete(k) => {
+                let expected = match model.remove(&k) {
+                    Some(_) => Ok(()),
+                    None => Err(TreeError::KeyNotFound),
+                };
+                if let Some(t) = tree.take() {
+                    let (result, t) = t.try_delete(k);
+                    prop_assert_eq!(result, expected);
+                    tree = t;
+                }
+            }
+            Op::Find(k) => {
+                let found = tree.as_ref().and_then(|t| t.get(&k)).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        match tree.as_ref() {
+            Some(t) => {
+                check(t.check_invariants())?;
+                prop_assert_eq!(t.len(), model.len());
+                assert_same(t.iter(), &model)?;
+            }
+            None => prop_assert!(model.is_empty()),
+        }
+    }
+    Ok(())
+}
+
+fn run_map(dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map = RbMap::new().with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_first() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_first());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(ops in ops(64, 200)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(ops in ops(8, 100)) {
+        run(ops)?;
+    }
+
+    #[test]
+    fn rb_map_matches_btreemap(dup in policies(), ops in ops(16, 200)) {
+        run_map(dup, ops)?;
+    }
+}
+
+fn tree_of(keys: impl IntoIterator<Item = u8>) -> Tree {
+    let mut keys = keys.into_iter();
+    let first = keys.next().unwrap();
+    let mut tree = Tree::new(first, first as i32);
+    for k in keys {
+        tree = tree.insert(k, k as i32);
+    }
+    tree
+}
+
+#[test]
+fn rejects_a_duplicate_without_touching_the_tree() {
+    let tree = tree_of((0..32u16).map(|i| (i * 13 % 32) as u8));
+    let (result, tree) = tree.try_insert(7, 700);
+    assert_eq!(result, Err(TreeError::DuplicateKey));
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.len(), 32);
+    assert_eq!(*tree.get(&7).unwrap().borrow(), 7);
+}
+
+#[test]
+fn edits_through_get_mut_and_entry() {
+    let tree = tree_of(0..20);
+    assert_eq!(tree.get_mut(&4, |v| std::mem::replace(v, 40)), Some(4));
+    assert_eq!(tree.get_mut(&20, |v| *v = 0), None);
+    assert_eq!(*tree.get(&4).unwrap().borrow(), 40);
+
+    let (tree, value) = tree.entry(5).and_modify(|v| *v += 1).or_insert(0);
+    assert_eq!(*value.borrow(), 6);
+    let entry = tree.entry(30);
+    assert!(matches!(entry, Entry::Vacant(_)));
+    let (tree, value) = entry.and_modify(|v| *v += 1).or_insert(300);
+    assert_eq!(*value.borrow(), 300);
+    tree.check_invariants().unwrap();
+    assert!(tree.contains_key(&30));
+}
+
+#[test]
+fn pops_from_both_ends() {
+    let mut tree = Some(tree_of((0..50u16).map(|i| (i * 17 % 50) as u8)));
+    let mut popped = Vec::new();
+    while let Some(t) = tree.take() {
+        let ((k, v), rest) = if popped.len() % 2 == 0 {
+            t.pop_first()
+        } else {
+            t.pop_last()
+        };
+        assert_eq!(*v.borrow(), k as i32);
+        if let Some(rest) = rest.as_ref() {
+            rest.check_invariants().unwrap();
+        }
+        popped.push(k);
+        tree = rest;
+    }
+    let expected: Vec<u8> = (0..25).flat_map(|i| [i, 49 - i]).collect();
+    assert_eq!(popped, expected);
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{plain::Plain, scapegoat::ScapegoatMap, BinTree},
+        Duplicates,
+    },
+};
+
+fn run(
+    map: ScapegoatMap<u8, i32>,
+    dup: Duplicates<i32>,
+    ops: Vec<Op>,
+) -> Result<(), TestCaseError> {
+    let mut map = map.with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_last() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_last());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(dup in policies(), ops in ops(64, 200)) {
+        run(ScapegoatMap::new(), dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(dup in policies(), ops in ops(8, 100)) {
+        run(ScapegoatMap::new(), dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_for_any_alpha(
+        alpha in 0.51f64..0.99,
+        dup in policies(),
+        ops in ops(64, 200),
+    ) {
+        run(ScapegoatMap::with_alpha(alpha), dup, ops)?;
+    }
+
+    #[test]
+    fn rebuild_keeps_the_pairs(keys in prop::collection::btree_set(any::<u8>(), 1..100)) {
+        let mut keys = keys.into_iter();
+        let first = keys.next().unwrap();
+        let tree = BinTree::new(first, i32::from(first));
+        for k in keys {
+            tree.try_insert(k, i32::from(k)).unwrap();
+        }
+        let model: BTreeMap<u8, i32> = tree.iter().map(|(k, v)| (k, *v.borrow())).collect();
+        let tree = tree.rebuild();
+        check(tree.check_invariants())?;
+        assert_same(tree.iter(), &model)?;
+    }
+}
+
+#[test]
+fn ascending_inserts_stay_shallow() {
+    let map: ScapegoatMap<u32, u32> = (0..10_000).map(|k| (k, k)).collect();
+    check_ok(&map);
+    assert_eq!(map.len(), 10_000);
+}
+
+// A plain tree that is a single path to the right, as ascending inserts leave it. The
+// plain insert walks down in a loop, so the length of the path is no concern.
+fn path(len: u32) -> BinTree<u32, u32, ()> {
+    let tree = BinTree::new(0, 0);
+    for k in 1..len {
+        tree.try_insert(k, k).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn retrofits_a_degenerate_plain_tree() {
+    let mut map = ScapegoatMap::from_root(path(2_000));
+    check_ok(&map);
+    for k in (0..2_000).step_by(3) {
+        map.remove(k).unwrap();
+        check_ok(&map);
+    }
+    assert_eq!(map.first().map(|(k, _)| k), Some(1));
+    assert!(map.into_root().is_some());
+}
+
+#[test]
+fn retrofits_with_the_given_alpha() {
+    for alpha in [0.55, 0.75, 0.95] {
+        let mut map = ScapegoatMap::from_root_with_alpha(path(500), alpha);
+        check_ok(&map);
+        // Ascending inserts on top have to keep within the bound of this alpha.
+        for k in 500..1_000 {
+            map.try_insert(k, k).unwrap();
+            check_ok(&map);
+        }
+        assert_eq!(map.len(), 1_000);
+        // The rebuilds come at other depths than with the default alpha.
+        let mut default = ScapegoatMap::from_root(path(500));
+        for k in 500..1_000 {
+            default.try_insert(k, k).unwrap();
+        }
+        let shape = |map: ScapegoatMap<u32, u32>| format!("{:?}", map.into_root());
+        assert_ne!(shape(map), shape(default));
+    }
+}
+
+fn check_ok<K: Ord + Copy + std::fmt::Debug, V>(map: &ScapegoatMap<K, V>) {
+    if let Err(e) = map.check_invariants() {
+        panic!("{}", e);
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{bintree::splay::SplayMap, Duplicates},
+};
+
+fn run_map(dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map = SplayMap::new().with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_first() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_first());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+// The key at the root, as the first one the debug output shows.
+fn root_key(map: SplayMap<u32, u32>) -> String {
+    let root = format!("{:?}", map.into_root().unwrap());
+    let key = root.split("key: ").nth(1).unwrap();
+    key[..key.find(',').unwrap()].to_string()
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(dup in policies(), ops in ops(64, 200)) {
+        run_map(dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(dup in policies(), ops in ops(8, 100)) {
+        run_map(dup, ops)?;
+    }
+
+    #[test]
+    fn found_keys_end_up_at_the_root(
+        keys in prop::collection::btree_set(0u32..1000, 1..100),
+        pick in any::<prop::sample::Index>(),
+    ) {
+        let map: SplayMap<u32, u32> = keys.iter().map(|k| (*k, *k)).collect();
+        let key = *pick.get(&keys.iter().copied().collect::<Vec<_>>());
+        prop_assert!(map.get(&key).is_some());
+        check(map.check_invariants())?;
+        prop_assert_eq!(root_key(map), key.to_string());
+    }
+}
+
+#[test]
+fn long_paths_do_not_overflow_the_stack() {
+    // Ascending inserts leave a path as deep as the map is large.
+    let mut map = SplayMap::new();
+    for k in 0..200_000u32 {
+        map.insert(k, k);
+    }
+    assert_eq!(*map.get(&0).unwrap().borrow(), 0);
+    assert_eq!(map.iter().count(), 200_000);
+    map.check_invariants().unwrap();
+    for k in (0..200_000u32).step_by(2) {
+        map.remove(k).unwrap();
+    }
+    assert_eq!(map.len(), 100_000);
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use std::{collections::BTreeMap, rc::Rc};
+
+use common::{assert_same, check, ops, policies, Op};
+use proptest::prelude::*;
+use tree::{
+    error::TreeError,
+    trees::{
+        bintree::{
+            treap::{Priority, PriorityRng, TreapMap, XorShift},
+            BinTree,
+        },
+        Duplicates,
+    },
+};
+
+fn run_map(seed: u64, dup: Duplicates<i32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map = TreapMap::with_rng(XorShift::new(seed)).with_duplicates(dup);
+    let mut model = BTreeMap::new();
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => {
+                let replaced = map.try_insert(k, v).map(|old| old.map(|v| *v.borrow()));
+                prop_assert_eq!(replaced, common::insert(&mut model, dup, k, v));
+            }
+            Op::Delete(k) => {
+                let removed = map.remove(k).map(|v| *v.borrow());
+                prop_assert_eq!(removed, model.remove(&k).ok_or(TreeError::KeyNotFound));
+            }
+            Op::Find(k) => {
+                let found = map.get(&k).map(|v| *v.borrow());
+                prop_assert_eq!(found, model.get(&k).copied());
+            }
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        prop_assert_eq!(map.is_empty(), model.is_empty());
+        assert_same(map.iter(), &model)?;
+    }
+    while let Some((k, v)) = map.pop_first() {
+        prop_assert_eq!(Some((k, *v.borrow())), model.pop_first());
+    }
+    prop_assert!(model.is_empty());
+    Ok(())
+}
+
+fn from_model(seed: u64, model: &BTreeMap<u8, i32>) -> TreapMap<u8, i32> {
+    let mut map = TreapMap::with_rng(XorShift::new(seed));
+    for (k, v) in model {
+        map.insert(*k, *v);
+    }
+    map
+}
+
+// Hands out ever larger priorities, so every insert ends up at the root.
+struct Ascending(u64);
+
+impl PriorityRng for Ascending {
+    fn next_u64(&mut self) -> u64 {
+        self.0 += 1;
+        self.0
+    }
+
+    fn fork(&mut self) -> Self {
+        Ascending(self.0)
+    }
+}
+
+proptest! {
+    #[test]
+    fn matches_btreemap(seed in any::<u64>(), dup in policies(), ops in ops(64, 200)) {
+        run_map(seed, dup, ops)?;
+    }
+
+    #[test]
+    fn matches_btreemap_on_few_keys(
+        seed in any::<u64>(),
+        dup in policies(),
+        ops in ops(8, 100),
+    ) {
+        run_map(seed, dup, ops)?;
+    }
+
+    #[test]
+    fn split_off_matches_btreemap(
+        seed in any::<u64>(),
+        model in prop::collection::btree_map(0u8..64, any::<i32>(), 0..64),
+        key in 0u8..70,
+    ) {
+        let mut map = from_model(seed, &model);
+        let mut model = model;
+        let upper = map.split_off(key);
+        let model_upper = model.split_off(&key);
+        check(map.check_invariants())?;
+        check(upper.check_invariants())?;
+        assert_same(map.iter(), &model)?;
+        assert_same(upper.iter(), &model_upper)?;
+    }
+
+    #[test]
+    fn append_matches_btreemap(
+        seed in any::<u64>(),
+        dup in policies(),
+        a in prop::collection::btree_map(0u8..64, any::<i32>(), 0..64),
+        b in prop::collection::btree_map(0u8..64, any::<i32>(), 0..64),
+    ) {
+        let mut map = from_model(seed, &a).with_duplicates(dup);
+        let mut other = from_model(seed.wrapping_add(1), &b);
+        let mut model = a.clone();
+        let expected = b
+            .iter()
+            .try_for_each(|(k, v)| common::insert(&mut model, dup, *k, *v).map(|_| ()));
+        prop_assert_eq!(map.append(&mut other), expected.clone());
+        if expected.is_err() {
+            // Both maps stay as they were.
+            model = a;
+            assert_same(other.iter(), &b)?;
+        } else {
+            prop_assert!(other.is_empty());
+        }
+        check(map.check_invariants())?;
+        prop_assert_eq!(map.len(), model.len());
+        assert_same(map.iter(), &model)?;
+    }
+
+    #[test]
+    fn same_seed_same_shape(seed in any::<u64>(), ops in ops(64, 100)) {
+        let mut a = TreapMap::with_rng(XorShift::new(seed));
+        let mut b = TreapMap::with_rng(XorShift::new(seed));
+        for op in ops {
+            if let Op::Insert(k, v) = op {
+                a.insert(k, v);
+                b.insert(k, v);
+            }
+        }
+        let (a, b) = (a.into_root(), b.into_root());
+        prop_assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    }
+}
+
+#[test]
+fn any_rng_can_be_plugged_in() {
+    let mut map = TreapMap::with_rng(Ascending(0));
+    for k in 0..100 {
+        map.insert(k, k);
+    }
+    map.check_invariants().unwrap();
+    assert_eq!(map.len(), 100);
+    // The last key drew the highest priority.
+    let root = format!("{:?}", map.into_root().unwrap());
+    let first_key = root.split("key: ").nth(1).unwrap();
+    assert!(first_key.starts_with("99,"));
+}
+
+#[test]
+#[should_panic(expected = "The key already exists.")]
+fn a_duplicate_key_panics() {
+    let mut root = BinTree::with_priority(5, 5, Priority(3));
+    for (k, p) in [(2, 1), (8, 2), (7, 4)] {
+        root = root.insert(k, k, Priority(p));
+    }
+    root.check_invariants().unwrap();
+    root.insert(8, 0, Priority(0));
+}
+
+#[test]
+fn a_rejected_duplicate_leaves_the_treap_as_it_was() {
+    let mut root = BinTree::with_priority(5, 5, Priority(3));
+    for (k, p) in [(2, 1), (8, 2), (7, 4)] {
+        root = root.insert(k, k, Priority(p));
+    }
+    let shape = format!("{:?}", root);
+    let (result, root) = root.try_insert(8, 0, Priority(9));
+    assert_eq!(result, Err(TreeError::DuplicateKey));
+    root.check_invariants().unwrap();
+    assert_eq!(format!("{:?}", root), shape);
+    assert_eq!(*root.get(&8).unwrap().borrow(), 8);
+}
+
+#[test]
+fn split_off_forks_the_generator() {
+    let mut map = TreapMap::with_rng(XorShift::new(7));
+    map.insert(0, 0);
+    let mut upper = map.split_off(0);
+    upper.remove(0).unwrap();
+    map.insert(1, 1);
+    upper.insert(1, 1);
+    let (map, upper) = (map.into_root(), upper.into_root());
+    assert_ne!(format!("{:?}", map), format!("{:?}", upper));
+}
+
+#[test]
+fn append_merges_into_the_stored_cell() {
+    let mut map = TreapMap::with_rng(XorShift::new(1)).with_duplicates(Duplicates::append());
+    let mut other = TreapMap::with_rng(XorShift::new(2));
+    for k in 0..20u8 {
+        map.insert(k, vec![k]);
+        other.insert(k + 10, vec![k + 10]);
+    }
+    let cell = map.get(&15).unwrap();
+    // A cell still handed out by `other` is cloned from.
+    let theirs = other.get(&15).unwrap();
+    map.append(&mut other).unwrap();
+    assert!(Rc::ptr_eq(&cell, &map.get(&15).unwrap()));
+    assert_eq!(*cell.borrow(), vec![15, 15]);
+    assert_eq!(*theirs.borrow(), vec![15]);
+    assert_eq!(map.len(), 30);
+}